@@ -0,0 +1,176 @@
+use actix_files::NamedFile;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{web, HttpRequest, HttpResponse};
+use rust_embed::RustEmbed;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::handlers::AppState;
+
+/// Job icons and default profile images, served at /assets
+#[derive(RustEmbed)]
+#[folder = "src/assets"]
+struct Assets;
+
+/// The stylesheet, served at /static
+#[derive(RustEmbed)]
+#[folder = "static"]
+struct Static;
+
+// A URL carrying the file's content hash (see `stylesheet_url`) names one exact
+// version, so browsers can keep it for a year. Everything else is fetched by a fixed
+// URL and must be rechecked, or a new release would stay hidden behind the cache.
+const VERSIONED_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+const UNVERSIONED_CACHE_CONTROL: &str = "public, max-age=3600, must-revalidate";
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The short content hash used as the `v` query parameter on versioned URLs
+fn version(hash: &[u8; 32]) -> String {
+    hex(&hash[..8])
+}
+
+/// The stylesheet URL, versioned by its content so browsers can cache it for a year
+pub fn stylesheet_url() -> &'static str {
+    static URL: OnceLock<String> = OnceLock::new();
+    URL.get_or_init(|| match Static::get("style.css") {
+        Some(file) => format!(
+            "/static/style.css?v={}",
+            version(&file.metadata.sha256_hash())
+        ),
+        None => "/static/style.css".to_string(),
+    })
+}
+
+/// Only a request naming the file's current version may be cached for good; a stale
+/// or missing `v` gets the short-lived header
+fn cache_control(query: &str, version: &str) -> &'static str {
+    let versioned = query
+        .split('&')
+        .any(|pair| pair.strip_prefix("v=") == Some(version));
+    if versioned {
+        VERSIONED_CACHE_CONTROL
+    } else {
+        UNVERSIONED_CACHE_CONTROL
+    }
+}
+
+/// Reject anything that could escape the asset directory or reach hidden files
+fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && !part.starts_with('.'))
+}
+
+/// A file from the override directory, when one is configured and has this path
+fn override_file(dir: Option<&Path>, prefix: &str, path: &str) -> Option<PathBuf> {
+    let candidate = dir?.join(prefix).join(path);
+    candidate.is_file().then_some(candidate)
+}
+
+fn serve<E: RustEmbed>(
+    req: &HttpRequest,
+    data: &AppState,
+    prefix: &str,
+    path: &str,
+) -> HttpResponse {
+    if !is_safe_path(path) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    if let Some(file) = override_file(data.assets_dir.as_deref(), prefix, path) {
+        return match NamedFile::open(&file) {
+            Ok(file) => file.use_last_modified(true).into_response(req),
+            Err(e) => {
+                log::error!("Failed to open {}: {}", file.display(), e);
+                HttpResponse::InternalServerError().finish()
+            }
+        };
+    }
+
+    let Some(file) = E::get(path) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let hash = file.metadata.sha256_hash();
+    let etag = format!("\"{}\"", hex(&hash));
+    let cache_control = cache_control(req.query_string(), &version(&hash));
+
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        });
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header((header::ETAG, etag)).insert_header((
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    ));
+
+    if not_modified {
+        response.finish()
+    } else {
+        response
+            .content_type(file.metadata.mimetype())
+            .body(file.data.into_owned())
+    }
+}
+
+pub async fn assets(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    serve::<Assets>(&req, &data, "assets", &path)
+}
+
+pub async fn static_files(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    serve::<Static>(&req, &data, "static", &path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_current_versioned_urls_are_immutable() {
+        assert_eq!(
+            cache_control("v=0123abcd", "0123abcd"),
+            VERSIONED_CACHE_CONTROL
+        );
+        assert_eq!(
+            cache_control("x=1&v=0123abcd", "0123abcd"),
+            VERSIONED_CACHE_CONTROL
+        );
+        assert_eq!(cache_control("", "0123abcd"), UNVERSIONED_CACHE_CONTROL);
+        assert_eq!(
+            cache_control("v=deadbeef", "0123abcd"),
+            UNVERSIONED_CACHE_CONTROL
+        );
+    }
+
+    #[test]
+    fn stylesheet_url_carries_the_current_version() {
+        let file = Static::get("style.css").expect("the stylesheet is embedded");
+        let (_, query) = stylesheet_url().split_once('?').expect("a versioned URL");
+        assert_eq!(
+            cache_control(query, &version(&file.metadata.sha256_hash())),
+            VERSIONED_CACHE_CONTROL
+        );
+    }
+}
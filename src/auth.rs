@@ -0,0 +1,117 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, SaltString};
+use argon2::{Argon2, PasswordVerifier};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+// Keeps obviously weak passwords out of the admins table
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+
+pub const SESSION_COOKIE: &str = "admin_session";
+pub const SESSION_HOURS: i64 = 12;
+// Admin pages send their session's CSRF token back in this header on mutations
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Hash a password into a PHC string suitable for storing in the admins table
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(e) => {
+            log::error!("Stored password hash is malformed: {e}");
+            false
+        }
+    }
+}
+
+/// A hash of a throwaway password with the same parameters as real ones. Logins for
+/// unknown usernames verify against it so they take as long as logins for real ones.
+pub fn dummy_password_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| {
+        hash_password(&to_hex(&random_bytes::<16>())).expect("hashing a random password succeeds")
+    })
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Constant-time comparison for the legacy shared ADMIN_PASSWORD
+pub fn passwords_match(provided: &str, expected: &str) -> bool {
+    constant_time_eq(provided.as_bytes(), expected.as_bytes())
+}
+
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// A new random session token to hand to the browser
+pub fn generate_token() -> String {
+    to_hex(&random_bytes::<32>())
+}
+
+/// Session tokens and recovery codes carry at least 128 random bits, too many to
+/// guess offline, so a plain SHA-256 is enough to keep them useless if the database
+/// leaks
+pub fn hash_token(token: &str) -> String {
+    to_hex(&Sha256::digest(token.as_bytes()))
+}
+
+/// One-time recovery codes of 128 random bits, in the form `xxxxxxxx-xxxxxxxx-xxxxxxxx-xxxxxxxx`
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let hex = to_hex(&random_bytes::<16>());
+            [&hex[..8], &hex[8..16], &hex[16..24], &hex[24..]].join("-")
+        })
+        .collect()
+}
+
+/// Recovery codes are compared case-insensitively and with or without the dash
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_ascii_lowercase().replace(['-', ' '], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_codes_carry_128_bits() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in &codes {
+            let normalized = normalize_recovery_code(code);
+            assert_eq!(normalized.len(), 32);
+            assert!(normalized.chars().all(|c| c.is_ascii_hexdigit()));
+            assert_eq!(normalize_recovery_code(&code.to_uppercase()), normalized);
+        }
+        let unique: std::collections::HashSet<_> = codes.iter().collect();
+        assert_eq!(unique.len(), codes.len());
+    }
+
+    #[test]
+    fn dummy_hash_is_a_real_hash_nothing_matches() {
+        let hash = dummy_password_hash();
+        assert!(PasswordHash::new(hash).is_ok());
+        assert!(!verify_password("", hash));
+        assert!(!verify_password("password123", hash));
+    }
+}
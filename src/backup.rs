@@ -0,0 +1,225 @@
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::db::SCHEMA_VERSION;
+use crate::pool::DbPool;
+
+const BACKUP_PREFIX: &str = "feedback-";
+const BACKUP_EXTENSION: &str = ".db";
+
+// Pages copied per step, with a short pause so the server can keep writing meanwhile
+const PAGES_PER_STEP: std::ffi::c_int = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error),
+    /// The file is not a backup this release can restore
+    Invalid(String),
+}
+
+impl std::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackupError::Io(e) => write!(f, "{e}"),
+            BackupError::Sqlite(e) => write!(f, "{e}"),
+            BackupError::Invalid(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<std::io::Error> for BackupError {
+    fn from(e: std::io::Error) -> Self {
+        BackupError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for BackupError {
+    fn from(e: rusqlite::Error) -> Self {
+        BackupError::Sqlite(e)
+    }
+}
+
+/// A backup file in the backup directory
+#[derive(Debug, Serialize)]
+pub struct BackupFile {
+    pub name: String,
+    pub size_bytes: u64,
+}
+
+/// Copy `src` into `dst` with SQLite's online backup API, which stays consistent
+/// while other connections keep using the source
+fn copy_database(src: &Connection, dst: &mut Connection) -> rusqlite::Result<()> {
+    let backup = Backup::new(src, dst)?;
+    backup.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+}
+
+/// A timestamped file name that isn't taken yet. The fixed-width timestamp keeps
+/// names in chronological order when sorted.
+fn next_backup_path(dir: &Path) -> PathBuf {
+    loop {
+        let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S-%3f");
+        let path = dir.join(format!("{BACKUP_PREFIX}{stamp}{BACKUP_EXTENSION}"));
+        if !path.exists() {
+            return path;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Write a timestamped copy of the database into `dir`. The copy is made under a
+/// temporary name first, so an interrupted backup never looks like a finished one.
+pub fn create_backup(conn: &Connection, dir: &Path) -> Result<PathBuf, BackupError> {
+    std::fs::create_dir_all(dir)?;
+    let path = next_backup_path(dir);
+    let partial = path.with_extension("db.partial");
+
+    let result = Connection::open(&partial)
+        .and_then(|mut dst| copy_database(conn, &mut dst))
+        .map_err(BackupError::from)
+        .and_then(|()| Ok(std::fs::rename(&partial, &path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result.map(|()| path)
+}
+
+fn is_backup_name(name: &str) -> bool {
+    name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_EXTENSION)
+}
+
+/// Backups in `dir`, newest first. A missing directory has no backups.
+pub fn list_backups(dir: &Path) -> std::io::Result<Vec<BackupFile>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut backups = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        if metadata.is_file() && is_backup_name(&name) {
+            backups.push(BackupFile {
+                name,
+                size_bytes: metadata.len(),
+            });
+        }
+    }
+    // Timestamped names sort chronologically
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+/// Delete all but the newest `keep` backups, returning how many were removed
+pub fn rotate_backups(dir: &Path, keep: usize) -> std::io::Result<usize> {
+    let backups = list_backups(dir)?;
+    let mut removed = 0;
+    for old in backups.iter().skip(keep) {
+        std::fs::remove_file(dir.join(&old.name))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Back up and rotate in one go, as the scheduler and the admin panel do
+pub fn run_backup(conn: &Connection, dir: &Path, keep: usize) -> Result<PathBuf, BackupError> {
+    let path = create_backup(conn, dir)?;
+    match rotate_backups(dir, keep) {
+        Ok(0) => {}
+        Ok(removed) => log::info!("Removed {removed} old backups"),
+        Err(e) => log::warn!("Failed to remove old backups: {e}"),
+    }
+    Ok(path)
+}
+
+/// Check that a file is an intact database with a schema this release understands
+fn validate_backup(path: &Path) -> Result<Connection, BackupError> {
+    if !path.is_file() {
+        return Err(BackupError::Invalid(format!(
+            "{} does not exist",
+            path.display()
+        )));
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let check: String = conn
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| BackupError::Invalid(format!("not a readable database ({e})")))?;
+    if check != "ok" {
+        return Err(BackupError::Invalid(format!(
+            "database is corrupt: {check}"
+        )));
+    }
+
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version == 0 {
+        return Err(BackupError::Invalid(
+            "not a FinalFeedback database (no schema version)".to_string(),
+        ));
+    }
+    if version > SCHEMA_VERSION {
+        return Err(BackupError::Invalid(format!(
+            "made by a newer release (schema version {version}, this release supports up to {SCHEMA_VERSION})"
+        )));
+    }
+
+    let has_feedback: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'feedback')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_feedback {
+        return Err(BackupError::Invalid(
+            "not a FinalFeedback database (no feedback table)".to_string(),
+        ));
+    }
+
+    Ok(conn)
+}
+
+/// Replace the database at `db_path` with a backup. The backup is validated first and
+/// the current database is backed up to `backup_dir`, returning that safety copy.
+pub fn restore_backup(
+    backup: &Path,
+    db_path: &str,
+    backup_dir: &Path,
+) -> Result<PathBuf, BackupError> {
+    let source = validate_backup(backup)?;
+
+    let mut live = Connection::open(db_path)?;
+    let safety_copy = create_backup(&live, backup_dir)?;
+    copy_database(&source, &mut live)?;
+    Ok(safety_copy)
+}
+
+/// Back up the database every `interval_hours`, the first time a full interval after
+/// startup. Copies are made from a reader, so the writer stays free.
+pub fn spawn_schedule(pool: DbPool, dir: PathBuf, interval_hours: u64, keep: usize) {
+    let period = std::time::Duration::from_secs(interval_hours * 60 * 60);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            let dir = dir.clone();
+            let result =
+                tokio::task::spawn_blocking(move || run_backup(&pool.reader(), &dir, keep)).await;
+            match result {
+                Ok(Ok(path)) => log::info!("Backed up database to {}", path.display()),
+                Ok(Err(e)) => log::error!("Scheduled backup failed: {e}"),
+                Err(e) => log::error!("Scheduled backup task failed: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,699 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::io::{self, BufRead, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use crate::auth::{hash_password, MIN_PASSWORD_LENGTH};
+use crate::backup;
+use crate::config::{Config, ConfigError};
+use crate::db;
+use crate::duties;
+use crate::jobs;
+use crate::models::{AuditAction, Feedback, FeedbackFilter, FeedbackSubmission, Role};
+use crate::pool::DbPool;
+use crate::repository::FeedbackRepository;
+use crate::store::FeedbackStore;
+use crate::validation::validate_feedback;
+
+pub const USAGE: &str = "Usage: final-feedback [--config <file>] [command]
+
+Commands:
+  serve                       Run the web server (the default)
+  check-config                Validate the configuration and report every problem
+  migrate                     Create or upgrade the database schema, then exit
+  stats                       Print feedback, ban and admin totals
+  export [--include-trash] [--output <file>]
+                              Write feedback as JSON to stdout or a file
+  import <file>               Add feedback from an export. Entries that already
+                              exist are skipped; nothing is added if any entry
+                              fails validation.
+  prune [--days <n>]          Purge trashed feedback older than n days
+                              (default: the configured trash retention)
+  delete <id> [--purge]       Move feedback to the trash, or remove it for good
+  ban <ip>                    Block an IP address from submitting feedback
+  backup [--list]             Back up the database now, or list existing backups
+  restore <file>              Replace the database with a backup, after checking
+                              it and backing up the current database
+  hash-password               Print an Argon2 hash of a password read from stdin
+  create-admin <username> [--role owner|moderator|viewer]
+                              Create an admin account (default role: owner).
+                              The password is read from stdin.
+
+Options:
+  --config <file>             Load settings from a TOML file. Environment
+                              variables override values from the file.";
+
+/// Pull `--config <file>` (or `--config=<file>`) out of the arguments, wherever it
+/// appears, leaving the command and its own arguments behind
+pub fn split_config_flag(
+    args: impl Iterator<Item = String>,
+) -> Result<(Option<PathBuf>, Vec<String>), String> {
+    let mut config_path = None;
+    let mut rest = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => return Err("--config needs a file path".to_string()),
+            }
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            config_path = Some(PathBuf::from(path));
+        } else {
+            rest.push(arg);
+        }
+    }
+
+    Ok((config_path, rest))
+}
+
+/// `check-config`, reports every configuration problem without starting the server
+pub fn check_config(path: Option<&Path>, config: &Result<Config, ConfigError>) -> i32 {
+    match path {
+        Some(path) => println!("Checking {} and environment overrides", path.display()),
+        None => println!("Checking environment configuration (no --config file given)"),
+    }
+
+    match config {
+        Ok(config) => {
+            println!("Configuration OK");
+            println!("  Listen:   {}:{}", config.host, config.port);
+            println!("  Database: {}", config.database_path);
+            if config.database_url.is_some() {
+                println!("  Feedback: PostgreSQL (database.url)");
+            }
+            println!(
+                "  Player:   {} @ {} ({})",
+                config.player.name, config.player.server, config.player.datacenter
+            );
+            if config.is_default_admin_password {
+                println!("  Warning:  ADMIN_PASSWORD is not set, the default password is in use");
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            1
+        }
+    }
+}
+
+/// Run a maintenance command against the configured database, returning the exit code
+pub fn run(command: &str, args: &[String], config: &Config) -> i32 {
+    match command {
+        "migrate" => migrate(config, args),
+        "stats" => stats(config, args),
+        "export" => export(config, args),
+        "import" => import(config, args),
+        "prune" => prune(config, args),
+        "delete" => delete(config, args),
+        "ban" => ban(config, args),
+        "backup" => backup_command(config, args),
+        "restore" => restore(config, args),
+        "hash-password" => hash_password_command(args),
+        "create-admin" => create_admin(&config.database_path, args),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            0
+        }
+        other => {
+            eprintln!("Unknown command: {other}\n\n{USAGE}");
+            2
+        }
+    }
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("{message}\n\n{USAGE}");
+    2
+}
+
+/// Reject arguments to commands that take none
+fn no_args(args: &[String]) -> Result<(), i32> {
+    match args.first() {
+        Some(arg) => Err(usage_error(&format!("Unexpected argument: {arg}"))),
+        None => Ok(()),
+    }
+}
+
+/// Open the database, running any pending migrations, the same way the server does
+fn open_database(db_path: &str) -> Result<Connection, i32> {
+    db::init_database(db_path).map_err(|e| {
+        eprintln!("Failed to open database: {e}");
+        1
+    })
+}
+
+/// Open the SQLite database and the feedback store the server would use, running any
+/// pending migrations on both
+fn open_store(config: &Config) -> Result<(DbPool, FeedbackStore), i32> {
+    let pool = DbPool::open(&config.database_path).map_err(|e| {
+        eprintln!("Failed to open database: {e}");
+        1
+    })?;
+    let store = FeedbackStore::open(config.database_url.as_deref(), pool.clone()).map_err(|e| {
+        eprintln!("Failed to open feedback database: {e}");
+        1
+    })?;
+    Ok((pool, store))
+}
+
+/// Record a command line change in the audit log alongside those made in the panel
+fn audit<T: Serialize>(
+    repo: &dyn FeedbackRepository,
+    action: AuditAction,
+    target: &str,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    let snapshot = |value: Option<&T>| value.and_then(|v| serde_json::to_string(v).ok());
+    if let Err(e) = repo.record_audit(
+        "cli",
+        "local",
+        action,
+        Some(target),
+        snapshot(before).as_deref(),
+        snapshot(after).as_deref(),
+    ) {
+        eprintln!("Warning: failed to write audit log entry: {e}");
+    }
+}
+
+/// `migrate`, brings the schema up to date without starting the server
+fn migrate(config: &Config, args: &[String]) -> i32 {
+    if let Err(code) = no_args(args) {
+        return code;
+    }
+    match open_store(config) {
+        Ok((_, store)) => {
+            println!("Database {} is up to date", config.database_path);
+            if config.database_url.is_some() {
+                println!("{} feedback database is up to date", store.backend());
+            }
+            0
+        }
+        Err(code) => code,
+    }
+}
+
+fn stats(config: &Config, args: &[String]) -> i32 {
+    if let Err(code) = no_args(args) {
+        return code;
+    }
+    let (pool, store) = match open_store(config) {
+        Ok(opened) => opened,
+        Err(code) => return code,
+    };
+
+    // Admin accounts are always in the SQLite database
+    let stats = store
+        .read_blocking(|repo| repo.stats())
+        .and_then(|stats| Ok((stats, db::count_admins(&pool.reader())?)));
+    match stats {
+        Ok((stats, admins)) => {
+            println!("Feedback:       {}", stats.live);
+            println!("  Published:    {}", stats.published);
+            println!("  In trash:     {}", stats.trashed);
+            let by_datacenter = config.worlds.count_by_datacenter(
+                stats
+                    .by_server
+                    .iter()
+                    .map(|(server, count)| (server.as_str(), *count)),
+            );
+            for dc in by_datacenter {
+                println!(
+                    "  {:<14}{}",
+                    format!("{} {}:", dc.region, dc.datacenter),
+                    dc.count
+                );
+            }
+            match stats.average_overall {
+                Some(average) => println!("Average rating: {average:.2}"),
+                None => println!("Average rating: -"),
+            }
+            for role in jobs::by_role(&stats.by_job) {
+                println!(
+                    "  {:<14}{:.2} ({})",
+                    format!("{}:", role.role.label()),
+                    role.average_overall,
+                    role.count
+                );
+            }
+            if !stats.by_duty.is_empty() {
+                println!("By duty, lowest rated first:");
+            }
+            for duty in duties::by_duty(&stats.by_duty) {
+                println!(
+                    "  {:.2} ({}) {}",
+                    duty.average_overall, duty.count, duty.duty
+                );
+            }
+            println!("Banned IPs:     {}", stats.banned_ips);
+            println!("Admin accounts: {admins}");
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to read stats: {e}");
+            1
+        }
+    }
+}
+
+/// `export [--include-trash] [--output <file>]`, in the format `import` reads back
+fn export(config: &Config, args: &[String]) -> i32 {
+    let mut include_trash = false;
+    let mut output = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--include-trash" => include_trash = true,
+            "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => return usage_error("--output needs a file path"),
+            },
+            other => return usage_error(&format!("Unexpected argument: {other}")),
+        }
+    }
+
+    let (_, store) = match open_store(config) {
+        Ok(opened) => opened,
+        Err(code) => return code,
+    };
+
+    let mut feedback = match store.read_blocking(|repo| repo.list(&FeedbackFilter::live())) {
+        Ok(feedback) => feedback,
+        Err(e) => {
+            eprintln!("Failed to read feedback: {e}");
+            return 1;
+        }
+    };
+    if include_trash {
+        match store.read_blocking(|repo| repo.list(&FeedbackFilter::trash())) {
+            Ok(trashed) => feedback.extend(trashed),
+            Err(e) => {
+                eprintln!("Failed to read trash: {e}");
+                return 1;
+            }
+        }
+    }
+
+    let json = match serde_json::to_string_pretty(&feedback) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to serialize feedback: {e}");
+            return 1;
+        }
+    };
+
+    let written = match &output {
+        Some(path) => std::fs::write(path, json + "\n"),
+        None => writeln!(io::stdout(), "{json}"),
+    };
+    if let Err(e) = written {
+        eprintln!("Failed to write export: {e}");
+        return 1;
+    }
+    if let Some(path) = output {
+        eprintln!(
+            "Exported {} feedback entries to {}",
+            feedback.len(),
+            path.display()
+        );
+    }
+    0
+}
+
+/// `import <file>`, all or nothing: any invalid entry rolls the whole import back
+fn import(config: &Config, args: &[String]) -> i32 {
+    let [path] = args else {
+        return usage_error("import needs exactly one file");
+    };
+
+    let mut feedback: Vec<Feedback> = match std::fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|contents| serde_json::from_str(&contents).map_err(|e| e.to_string()))
+    {
+        Ok(feedback) => feedback,
+        Err(e) => {
+            eprintln!("Failed to read {path}: {e}");
+            return 1;
+        }
+    };
+
+    // Entries go through the same checks as the form, keeping what only an export has
+    let mut invalid = 0;
+    for entry in &mut feedback {
+        match validate_feedback(&FeedbackSubmission::from(&*entry), &[], &config.worlds) {
+            Ok(validated) => {
+                *entry = Feedback {
+                    deleted_at: entry.deleted_at.take(),
+                    is_published: entry.is_published,
+                    tags: std::mem::take(&mut entry.tags),
+                    ..validated.into_feedback(
+                        std::mem::take(&mut entry.id),
+                        std::mem::take(&mut entry.ip_address),
+                        std::mem::take(&mut entry.created_at),
+                    )
+                };
+            }
+            Err(errors) => {
+                eprintln!("Invalid feedback {}: {errors}", entry.id);
+                invalid += 1;
+            }
+        }
+    }
+    if invalid > 0 {
+        eprintln!("Nothing imported, {invalid} entries are invalid");
+        return 1;
+    }
+
+    let (_, store) = match open_store(config) {
+        Ok(opened) => opened,
+        Err(code) => return code,
+    };
+
+    let result = store.write_blocking(|repo| {
+        let mut imported = 0;
+        for entry in &feedback {
+            if repo
+                .insert(entry)
+                .inspect_err(|_| eprintln!("Failed to import feedback {}", entry.id))?
+            {
+                imported += 1;
+            }
+        }
+        Ok(imported)
+    });
+    let imported = match result {
+        Ok(imported) => imported,
+        Err(e) => {
+            eprintln!("Import rolled back: {e}");
+            return 1;
+        }
+    };
+    println!(
+        "Imported {imported} feedback entries, skipped {} that already existed",
+        feedback.len() - imported
+    );
+    0
+}
+
+fn prune(config: &Config, args: &[String]) -> i32 {
+    let days = match args {
+        [] => config.trash_retention_days,
+        [flag, days] if flag == "--days" => match days.parse::<i64>() {
+            Ok(days) if days >= 0 => days,
+            _ => return usage_error("--days must be a whole number of days"),
+        },
+        _ => return usage_error("prune only accepts --days <n>"),
+    };
+
+    let (_, store) = match open_store(config) {
+        Ok(opened) => opened,
+        Err(code) => return code,
+    };
+
+    match store.write_blocking(|repo| repo.purge_expired(days)) {
+        Ok(purged) => {
+            println!(
+                "Purged {purged} feedback entries that were in the trash for over {days} days"
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to purge trash: {e}");
+            1
+        }
+    }
+}
+
+/// `delete <id> [--purge]`. Purging works on live or trashed feedback.
+fn delete(config: &Config, args: &[String]) -> i32 {
+    let (id, purge) = match args {
+        [id] => (id, false),
+        [id, flag] | [flag, id] if flag == "--purge" => (id, true),
+        _ => return usage_error("delete needs a feedback id"),
+    };
+
+    let (_, store) = match open_store(config) {
+        Ok(opened) => opened,
+        Err(code) => return code,
+    };
+
+    let result = store.write_blocking(|repo| {
+        let Some(before) = repo.get(id)? else {
+            return Ok(None);
+        };
+        let trashed = repo.soft_delete(id)?;
+        if trashed {
+            let after = repo.get(id)?;
+            audit(repo, AuditAction::Delete, id, Some(&before), after.as_ref());
+        }
+        if purge && repo.purge(id)? {
+            audit(repo, AuditAction::Purge, id, Some(&before), None);
+        }
+        Ok(Some(trashed))
+    });
+
+    match result {
+        Ok(None) => {
+            eprintln!("Feedback {id} not found");
+            1
+        }
+        Ok(Some(_)) if purge => {
+            println!("Purged feedback {id}");
+            0
+        }
+        Ok(Some(true)) => {
+            println!("Moved feedback {id} to the trash");
+            0
+        }
+        Ok(Some(false)) => {
+            println!("Feedback {id} is already in the trash");
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to delete feedback: {e}");
+            1
+        }
+    }
+}
+
+fn ban(config: &Config, args: &[String]) -> i32 {
+    let [ip] = args else {
+        return usage_error("ban needs exactly one IP address");
+    };
+    let Ok(ip) = ip.parse::<IpAddr>() else {
+        return usage_error(&format!("'{ip}' is not an IP address"));
+    };
+    let ip = ip.to_string();
+
+    let (_, store) = match open_store(config) {
+        Ok(opened) => opened,
+        Err(code) => return code,
+    };
+
+    let result = store.write_blocking(|repo| {
+        repo.ban_ip(&ip)?;
+        audit::<()>(repo, AuditAction::Ban, &ip, None, None);
+        Ok(())
+    });
+    match result {
+        Ok(()) => {
+            println!("Banned {ip}");
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to ban {ip}: {e}");
+            1
+        }
+    }
+}
+
+/// `backup [--list]`, uses the same directory and retention as scheduled backups
+fn backup_command(config: &Config, args: &[String]) -> i32 {
+    let dir = Path::new(&config.backup_dir);
+
+    match args {
+        [] => {}
+        [flag] if flag == "--list" => {
+            return match backup::list_backups(dir) {
+                Ok(backups) if backups.is_empty() => {
+                    println!("No backups in {}", dir.display());
+                    0
+                }
+                Ok(backups) => {
+                    for backup in backups {
+                        println!("{}  {} KB", backup.name, backup.size_bytes / 1024);
+                    }
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Failed to list backups: {e}");
+                    1
+                }
+            };
+        }
+        _ => return usage_error("backup only accepts --list"),
+    }
+
+    let conn = match open_database(&config.database_path) {
+        Ok(conn) => conn,
+        Err(code) => return code,
+    };
+    match backup::run_backup(&conn, dir, config.backup_keep) {
+        Ok(path) => {
+            println!("Backed up {} to {}", config.database_path, path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("Backup failed: {e}");
+            1
+        }
+    }
+}
+
+/// `restore <file>`, where the file is a path or the name of a file in the backup directory
+fn restore(config: &Config, args: &[String]) -> i32 {
+    let [file] = args else {
+        return usage_error("restore needs exactly one backup file");
+    };
+    let dir = Path::new(&config.backup_dir);
+    let path = match Path::new(file) {
+        path if path.exists() => path.to_path_buf(),
+        _ => dir.join(file),
+    };
+
+    match backup::restore_backup(&path, &config.database_path, dir) {
+        Ok(safety_copy) => {
+            println!("Previous database saved to {}", safety_copy.display());
+        }
+        Err(e) => {
+            eprintln!("Cannot restore {}: {e}", path.display());
+            return 1;
+        }
+    }
+
+    // Bring an older backup up to the current schema
+    if let Err(code) = open_database(&config.database_path) {
+        return code;
+    }
+    println!("Restored {} from {}", config.database_path, path.display());
+    0
+}
+
+/// `hash-password`, for setting an admin password directly in the database
+fn hash_password_command(args: &[String]) -> i32 {
+    if let Err(code) = no_args(args) {
+        return code;
+    }
+    let password = match read_password("Password") {
+        Ok(password) => password,
+        Err(e) => {
+            eprintln!("Failed to read password: {e}");
+            return 1;
+        }
+    };
+    if password.is_empty() {
+        eprintln!("Password must not be empty");
+        return 2;
+    }
+
+    match hash_password(&password) {
+        Ok(hash) => {
+            println!("{hash}");
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to hash password: {e}");
+            1
+        }
+    }
+}
+
+/// Read one line from stdin, so passwords can be typed or piped in from a script.
+/// The prompt goes to stderr to keep stdout clean for scripts.
+fn read_password(prompt: &str) -> io::Result<String> {
+    eprint!("{prompt}: ");
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// `create-admin <username> [--role <role>]`, mainly used to bootstrap the first owner
+fn create_admin(db_path: &str, args: &[String]) -> i32 {
+    let mut username = None;
+    let mut role = Role::Owner;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--role" => match args.next().and_then(|r| Role::parse(r)) {
+                Some(r) => role = r,
+                None => {
+                    eprintln!("--role must be one of: owner, moderator, viewer");
+                    return 2;
+                }
+            },
+            other if username.is_none() && !other.starts_with("--") => {
+                username = Some(other.to_string())
+            }
+            other => {
+                eprintln!("Unexpected argument: {other}\n\n{USAGE}");
+                return 2;
+            }
+        }
+    }
+
+    let Some(username) = username else {
+        eprintln!("{USAGE}");
+        return 2;
+    };
+
+    let password = match read_password(&format!(
+        "Password for new admin (min {MIN_PASSWORD_LENGTH} characters)"
+    )) {
+        Ok(password) => password,
+        Err(e) => {
+            eprintln!("Failed to read password: {e}");
+            return 1;
+        }
+    };
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        eprintln!("Password must be at least {MIN_PASSWORD_LENGTH} characters");
+        return 2;
+    }
+
+    let conn = match open_database(db_path) {
+        Ok(conn) => conn,
+        Err(code) => return code,
+    };
+
+    if role != Role::Owner && db::count_admins(&conn).unwrap_or(0) == 0 {
+        eprintln!("The first admin account must be an owner");
+        return 2;
+    }
+
+    let password_hash = match hash_password(&password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("Failed to hash password: {e}");
+            return 1;
+        }
+    };
+
+    match db::create_admin(&conn, &username, &password_hash, role) {
+        Ok(_) => {
+            println!("Created {} account '{username}'", role.as_str());
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to create admin: {e}");
+            1
+        }
+    }
+}
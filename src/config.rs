@@ -0,0 +1,589 @@
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::models::PlayerSettings;
+use crate::proxy::TrustedProxies;
+use crate::security::{self, SecurityHeaders};
+pub use crate::templates::PlayerConfig;
+use crate::worlds::WorldRegistry;
+
+const DEFAULT_ADMIN_PASSWORD: &str = "admin123";
+const PLACEHOLDER_SERVER: &str = "Server";
+const PLACEHOLDER_DATACENTER: &str = "Datacenter";
+
+// Each section of the TOML file. Every key is optional so a file only needs the
+// settings it changes, and unknown keys are rejected to catch typos.
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerSection {
+    host: Option<String>,
+    port: Option<u16>,
+    trusted_proxy_ips: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseSection {
+    path: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AdminSection {
+    password: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlayerSection {
+    name: Option<String>,
+    server: Option<String>,
+    datacenter: Option<String>,
+    banner_image: Option<String>,
+    profile_image: Option<String>,
+    tagline: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DiscordSection {
+    webhook_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SubmissionsSection {
+    rate_limit_minutes: Option<i64>,
+    ip_rate_limit_max: Option<i64>,
+    filter_words: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TrashSection {
+    retention_days: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BackupSection {
+    dir: Option<String>,
+    interval_hours: Option<u64>,
+    keep: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AssetsSection {
+    dir: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct UploadsSection {
+    dir: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SecuritySection {
+    csp_img_src: Option<Vec<String>>,
+    hsts_max_age: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DataSection {
+    worlds_file: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    server: ServerSection,
+    database: DatabaseSection,
+    backup: BackupSection,
+    admin: AdminSection,
+    player: PlayerSection,
+    discord: DiscordSection,
+    submissions: SubmissionsSection,
+    trash: TrashSection,
+    assets: AssetsSection,
+    uploads: UploadsSection,
+    security: SecuritySection,
+    data: DataSection,
+}
+
+/// Fully resolved and validated application settings
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub database_path: String,
+    /// PostgreSQL connection string. When set, feedback, bans, rate-limit counters and
+    /// the audit log are stored there instead of in the SQLite database.
+    pub database_url: Option<String>,
+    pub backup_dir: String,
+    /// Hours between scheduled backups, 0 when only manual backups are made
+    pub backup_interval_hours: u64,
+    /// How many backups to keep before the oldest are deleted
+    pub backup_keep: usize,
+    /// Files here take precedence over the assets built into the binary
+    pub assets_dir: Option<String>,
+    /// Where images uploaded from the settings page are stored
+    pub uploads_dir: String,
+    pub admin_password: String,
+    /// True when no admin password was configured and the default is in use
+    pub is_default_admin_password: bool,
+    pub discord_webhook_url: Option<String>,
+    pub player: PlayerConfig,
+    pub rate_limit_minutes: i64,
+    pub ip_rate_limit_max: i64,
+    pub trash_retention_days: i64,
+    pub trusted_proxies: TrustedProxies,
+    pub filter_words: Vec<String>,
+    pub security: SecurityHeaders,
+    /// Replaces the built-in world list when set
+    pub worlds_file: Option<String>,
+    pub worlds: Arc<WorldRegistry>,
+}
+
+/// The settings that can change while the server is running, see `reload`
+#[derive(Debug, Clone)]
+pub struct LiveSettings {
+    /// The player profile shown on every page, with the settings page overrides applied
+    pub player: PlayerConfig,
+    /// The player profile from the config file and environment
+    pub player_defaults: PlayerConfig,
+    /// Values saved from the admin settings page
+    pub player_overrides: PlayerSettings,
+    pub filter_words: Vec<String>,
+    pub discord_webhook_url: Option<String>,
+    pub security: SecurityHeaders,
+    /// Known worlds and data centers, for validation and the world pickers
+    pub worlds: Arc<WorldRegistry>,
+    /// Security headers before the override image hosts are allowed
+    security_base: SecurityHeaders,
+}
+
+impl LiveSettings {
+    /// The same settings with new player profile overrides applied
+    pub fn with_player_overrides(&self, overrides: PlayerSettings) -> LiveSettings {
+        let pick = |value: &Option<String>, default: &String| {
+            value.clone().unwrap_or_else(|| default.clone())
+        };
+        let defaults = &self.player_defaults;
+        let player = PlayerConfig {
+            name: pick(&overrides.name, &defaults.name),
+            server: pick(&overrides.server, &defaults.server),
+            datacenter: pick(&overrides.datacenter, &defaults.datacenter),
+            banner_image: pick(&overrides.banner_image, &defaults.banner_image),
+            profile_image: pick(&overrides.profile_image, &defaults.profile_image),
+            tagline: pick(&overrides.tagline, &defaults.tagline),
+        };
+
+        // Images saved from the settings page may live on another host
+        let security = self.security_base.with_img_sources(
+            [&player.banner_image, &player.profile_image]
+                .into_iter()
+                .filter_map(|url| security::url_origin(url)),
+        );
+
+        LiveSettings {
+            player,
+            player_overrides: overrides,
+            security,
+            ..self.clone()
+        }
+    }
+}
+
+/// Shared handle to the current `LiveSettings`. Readers take a cheap snapshot and a
+/// reload swaps in a complete new value, so no request sees a half-applied change.
+#[derive(Debug, Clone)]
+pub struct SharedSettings(Arc<RwLock<Arc<LiveSettings>>>);
+
+impl SharedSettings {
+    pub fn new(settings: LiveSettings) -> Self {
+        SharedSettings(Arc::new(RwLock::new(Arc::new(settings))))
+    }
+
+    pub fn load(&self) -> Arc<LiveSettings> {
+        self.0.read().clone()
+    }
+
+    /// Replace the settings with a value derived from the current ones, without
+    /// racing a concurrent reload
+    pub fn update(&self, f: impl FnOnce(&LiveSettings) -> LiveSettings) {
+        let mut current = self.0.write();
+        *current = Arc::new(f(&current));
+    }
+}
+
+/// Every problem found while loading the configuration, so they can all be fixed at once
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Variables from a `.env` file, see `read_env_file`
+pub type EnvFile = HashMap<String, String>;
+
+/// Find `.env` in the working directory or one of its parents, like `dotenvy::dotenv`
+pub fn find_env_file() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(".env"))
+        .find(|path| path.is_file())
+}
+
+/// Parse a `.env` file without applying it to the process environment, which other
+/// threads may be reading
+pub fn read_env_file(path: &Path) -> Result<EnvFile, dotenvy::Error> {
+    dotenvy::from_path_iter(path)?.collect()
+}
+
+/// Where environment overrides are read from: the real environment, then `.env`
+struct EnvOverrides<'a> {
+    env_file: &'a EnvFile,
+}
+
+impl EnvOverrides<'_> {
+    /// Empty values count as unset, so a `.env` file can list a key without a value
+    fn value(&self, name: &str) -> Option<String> {
+        env::var(name)
+            .ok()
+            .or_else(|| self.env_file.get(name).cloned())
+            .filter(|value| !value.trim().is_empty())
+    }
+
+    fn string(&self, slot: &mut Option<String>, name: &str) {
+        if let Some(value) = self.value(name) {
+            *slot = Some(value);
+        }
+    }
+
+    fn parsed<T: FromStr>(&self, slot: &mut Option<T>, name: &str, problems: &mut Vec<String>) {
+        if let Some(value) = self.value(name) {
+            match value.trim().parse() {
+                Ok(parsed) => *slot = Some(parsed),
+                Err(_) => problems.push(format!("{name}: '{value}' is not a valid number")),
+            }
+        }
+    }
+
+    /// Lists are comma-separated in the environment and arrays in the file
+    fn list(&self, slot: &mut Option<Vec<String>>, name: &str) {
+        if let Some(value) = self.value(name) {
+            *slot = Some(value.split(',').map(|item| item.to_string()).collect());
+        }
+    }
+}
+
+fn clean_list(list: Option<Vec<String>>) -> Vec<String> {
+    list.unwrap_or_default()
+        .into_iter()
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn require_positive(value: i64, key: &str, problems: &mut Vec<String>) {
+    if value < 1 {
+        problems.push(format!("{key}: must be at least 1, got {value}"));
+    }
+}
+
+fn validate_webhook_url(url: &str) -> Result<(), String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("'{url}' is not a valid URL ({e})"))?;
+    if parsed.scheme() != "https" {
+        return Err(format!("'{url}' must use https"));
+    }
+    if parsed.host_str().is_none() {
+        return Err(format!("'{url}' has no host"));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// The live settings for this configuration with the saved player overrides applied
+    pub fn live_settings(&self, player_overrides: PlayerSettings) -> LiveSettings {
+        LiveSettings {
+            player: self.player.clone(),
+            player_defaults: self.player.clone(),
+            player_overrides: PlayerSettings::default(),
+            filter_words: self.filter_words.clone(),
+            discord_webhook_url: self.discord_webhook_url.clone(),
+            security: self.security.clone(),
+            security_base: self.security.clone(),
+            worlds: self.worlds.clone(),
+        }
+        .with_player_overrides(player_overrides)
+    }
+
+    /// Load settings from an optional TOML file, then apply environment variable
+    /// overrides and validate the result
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        Config::load_with_env_file(path, &EnvFile::new())
+    }
+
+    /// Like `load`, with variables from a `.env` file applied wherever the real
+    /// environment doesn't set them
+    pub fn load_with_env_file(
+        path: Option<&Path>,
+        env_file: &EnvFile,
+    ) -> Result<Config, ConfigError> {
+        let env = EnvOverrides { env_file };
+        let mut problems = Vec::new();
+
+        let mut file = match path {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(contents) => toml::from_str::<FileConfig>(&contents).unwrap_or_else(|e| {
+                    problems.push(format!("{}: {}", path.display(), e.message()));
+                    FileConfig::default()
+                }),
+                Err(e) => {
+                    problems.push(format!("{}: {e}", path.display()));
+                    FileConfig::default()
+                }
+            },
+            None => FileConfig::default(),
+        };
+
+        env.string(&mut file.server.host, "HOST");
+        env.parsed(&mut file.server.port, "PORT", &mut problems);
+        env.list(&mut file.server.trusted_proxy_ips, "TRUSTED_PROXY_IPS");
+        env.string(&mut file.database.path, "DATABASE_PATH");
+        env.string(&mut file.database.url, "DATABASE_URL");
+        env.string(&mut file.backup.dir, "BACKUP_DIR");
+        env.parsed(
+            &mut file.backup.interval_hours,
+            "BACKUP_INTERVAL_HOURS",
+            &mut problems,
+        );
+        env.parsed(&mut file.backup.keep, "BACKUP_KEEP", &mut problems);
+        env.string(&mut file.assets.dir, "ASSETS_DIR");
+        env.string(&mut file.uploads.dir, "UPLOADS_DIR");
+        env.string(&mut file.admin.password, "ADMIN_PASSWORD");
+        env.string(&mut file.player.name, "PLAYER_NAME");
+        env.string(&mut file.player.server, "PLAYER_SERVER");
+        env.string(&mut file.player.datacenter, "PLAYER_DATACENTER");
+        env.string(&mut file.player.banner_image, "BANNER_IMAGE");
+        env.string(&mut file.player.profile_image, "PROFILE_IMAGE");
+        env.string(&mut file.player.tagline, "TAGLINE");
+        env.string(&mut file.discord.webhook_url, "DISCORD_WEBHOOK_URL");
+        env.parsed(
+            &mut file.submissions.rate_limit_minutes,
+            "RATE_LIMIT_MINUTES",
+            &mut problems,
+        );
+        env.parsed(
+            &mut file.submissions.ip_rate_limit_max,
+            "IP_RATE_LIMIT_MAX",
+            &mut problems,
+        );
+        env.list(&mut file.submissions.filter_words, "FILTER_WORDS");
+        env.parsed(
+            &mut file.trash.retention_days,
+            "TRASH_RETENTION_DAYS",
+            &mut problems,
+        );
+        env.string(&mut file.data.worlds_file, "WORLDS_FILE");
+        env.list(&mut file.security.csp_img_src, "CSP_IMG_SRC");
+        env.parsed(
+            &mut file.security.hsts_max_age,
+            "HSTS_MAX_AGE",
+            &mut problems,
+        );
+
+        let port = file.server.port.unwrap_or(8080);
+        if port == 0 {
+            problems.push("server.port (PORT): must be between 1 and 65535".to_string());
+        }
+
+        let rate_limit_minutes = file.submissions.rate_limit_minutes.unwrap_or(30);
+        require_positive(
+            rate_limit_minutes,
+            "submissions.rate_limit_minutes (RATE_LIMIT_MINUTES)",
+            &mut problems,
+        );
+        let ip_rate_limit_max = file.submissions.ip_rate_limit_max.unwrap_or(10);
+        require_positive(
+            ip_rate_limit_max,
+            "submissions.ip_rate_limit_max (IP_RATE_LIMIT_MAX)",
+            &mut problems,
+        );
+        let backup_keep = file.backup.keep.unwrap_or(7);
+        require_positive(backup_keep, "backup.keep (BACKUP_KEEP)", &mut problems);
+        let trash_retention_days = file.trash.retention_days.unwrap_or(30);
+        require_positive(
+            trash_retention_days,
+            "trash.retention_days (TRASH_RETENTION_DAYS)",
+            &mut problems,
+        );
+
+        let database_url = file.database.url.filter(|url| !url.trim().is_empty());
+        if database_url.is_some() && !cfg!(feature = "postgres") {
+            problems.push(
+                "database.url (DATABASE_URL): this build has no PostgreSQL support, rebuild with --features postgres"
+                    .to_string(),
+            );
+        }
+
+        let discord_webhook_url = file
+            .discord
+            .webhook_url
+            .filter(|url| !url.trim().is_empty());
+        if let Some(url) = &discord_webhook_url {
+            if let Err(problem) = validate_webhook_url(url) {
+                problems.push(format!(
+                    "discord.webhook_url (DISCORD_WEBHOOK_URL): {problem}"
+                ));
+            }
+        }
+
+        let worlds_file = file.data.worlds_file.filter(|path| !path.trim().is_empty());
+        let worlds =
+            WorldRegistry::load(worlds_file.as_deref().map(Path::new)).unwrap_or_else(|e| {
+                problems.push(format!("data.worlds_file (WORLDS_FILE): {e}"));
+                WorldRegistry::default()
+            });
+
+        // An unset world keeps the placeholder, but a configured one must be real
+        let player_server = match file.player.server {
+            Some(server) if !worlds.is_valid_server(&server) => {
+                problems.push(format!(
+                    "player.server (PLAYER_SERVER): '{server}' is not a known FFXIV world"
+                ));
+                server
+            }
+            Some(server) => server,
+            None => PLACEHOLDER_SERVER.to_string(),
+        };
+        // A configured data center must exist and be the player world's own
+        let player_datacenter = match file.player.datacenter {
+            Some(datacenter) => match worlds.datacenter_named(&datacenter) {
+                None => {
+                    problems.push(format!(
+                        "player.datacenter (PLAYER_DATACENTER): '{datacenter}' is not a known FFXIV data center"
+                    ));
+                    datacenter
+                }
+                Some(dc) => {
+                    if let Some(home) = worlds.find_datacenter(&player_server) {
+                        if home != dc {
+                            problems.push(format!(
+                                "player.datacenter (PLAYER_DATACENTER): {player_server} is on {}, not {}",
+                                home.name, dc.name
+                            ));
+                        }
+                    }
+                    dc.name.clone()
+                }
+            },
+            None => PLACEHOLDER_DATACENTER.to_string(),
+        };
+
+        let (trusted_proxies, invalid_proxies) =
+            TrustedProxies::parse_list(&clean_list(file.server.trusted_proxy_ips).join(","));
+        for entry in invalid_proxies {
+            problems.push(format!(
+                "server.trusted_proxy_ips (TRUSTED_PROXY_IPS): '{entry}' is not an IP address or CIDR range"
+            ));
+        }
+
+        let player = PlayerConfig {
+            name: file
+                .player
+                .name
+                .unwrap_or_else(|| "Your Character".to_string()),
+            server: player_server,
+            datacenter: player_datacenter,
+            banner_image: file
+                .player
+                .banner_image
+                .unwrap_or_else(|| "/assets/banner.webp".to_string()),
+            profile_image: file
+                .player
+                .profile_image
+                .unwrap_or_else(|| "/assets/profile.webp".to_string()),
+            tagline: file
+                .player
+                .tagline
+                .unwrap_or_else(|| "Ran content with me? Let me know how I did!".to_string()),
+        };
+
+        // External BANNER_IMAGE and PROFILE_IMAGE hosts are always allowed to load
+        let img_sources: Vec<String> = [&player.banner_image, &player.profile_image]
+            .into_iter()
+            .filter_map(|url| security::url_origin(url))
+            .chain(clean_list(file.security.csp_img_src))
+            .collect();
+        let (security, invalid_sources) =
+            SecurityHeaders::new(img_sources, file.security.hsts_max_age);
+        for entry in invalid_sources {
+            problems.push(format!(
+                "security.csp_img_src (CSP_IMG_SRC): '{entry}' is not a valid CSP source"
+            ));
+        }
+
+        if !problems.is_empty() {
+            return Err(ConfigError { problems });
+        }
+
+        let (admin_password, is_default_admin_password) = match file.admin.password {
+            Some(password) => (password, false),
+            None => (DEFAULT_ADMIN_PASSWORD.to_string(), true),
+        };
+
+        Ok(Config {
+            host: file.server.host.unwrap_or_else(|| "127.0.0.1".to_string()),
+            port,
+            database_path: file
+                .database
+                .path
+                .unwrap_or_else(|| "feedback.db".to_string()),
+            database_url,
+            backup_dir: file.backup.dir.unwrap_or_else(|| "backups".to_string()),
+            backup_interval_hours: file.backup.interval_hours.unwrap_or(24),
+            backup_keep: backup_keep as usize,
+            assets_dir: file.assets.dir,
+            uploads_dir: file.uploads.dir.unwrap_or_else(|| "uploads".to_string()),
+            admin_password,
+            is_default_admin_password,
+            discord_webhook_url,
+            player,
+            rate_limit_minutes,
+            ip_rate_limit_max,
+            trash_retention_days,
+            trusted_proxies,
+            filter_words: clean_list(file.submissions.filter_words)
+                .into_iter()
+                .map(|word| word.to_lowercase())
+                .collect(),
+            security,
+            worlds_file,
+            worlds: Arc::new(worlds),
+        })
+    }
+}
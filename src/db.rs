@@ -1,8 +1,26 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, Result, Row};
+
+use crate::models::{
+    AdminAccount, AuditAction, AuditEntry, AuditFilter, Feedback, FeedbackFilter, FeedbackStats,
+    PlayerSettings, RatingTally, Role,
+};
+
+/// Stored in `PRAGMA user_version` once `init_database` has brought a database up to
+/// date. Bump it whenever the schema changes, so backups from a newer release are
+/// never restored into an older one.
+pub const SCHEMA_VERSION: i64 = 2;
 
 pub fn init_database(db_path: &str) -> Result<Connection> {
     let conn = Connection::open(db_path)?;
 
+    // Needed for feedback_tags to be cleaned up when feedback is purged
+    conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
+    // Write-ahead logging lets readers run alongside the writer. It is stored in the
+    // database file, so the pool's reader connections pick it up too.
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS feedback (
             id TEXT PRIMARY KEY,
@@ -26,6 +44,18 @@ pub fn init_database(db_path: &str) -> Result<Connection> {
     // Migration: Add player_job column if it doesn't exist (for existing databases)
     let _ = conn.execute("ALTER TABLE feedback ADD COLUMN player_job TEXT", []);
 
+    // Migration: Add deleted_at column for the admin trash bin
+    let _ = conn.execute("ALTER TABLE feedback ADD COLUMN deleted_at TEXT", []);
+
+    // Migration: Add is_published column for admin moderation
+    let _ = conn.execute(
+        "ALTER TABLE feedback ADD COLUMN is_published INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Migration: Add duty column for the duty picked from the catalogue
+    let _ = conn.execute("ALTER TABLE feedback ADD COLUMN duty TEXT", []);
+
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_feedback_created_at ON feedback (created_at)",
         [],
@@ -36,6 +66,107 @@ pub fn init_database(db_path: &str) -> Result<Connection> {
         [],
     )?;
 
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_feedback_deleted_at ON feedback (deleted_at)",
+        [],
+    )?;
+
+    // Free-form admin tags attached to feedback
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS feedback_tags (
+            feedback_id TEXT NOT NULL REFERENCES feedback (id) ON DELETE CASCADE,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (feedback_id, tag)
+        )",
+        [],
+    )?;
+
+    // IPs blocked from submitting feedback
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS banned_ips (
+            ip_address TEXT PRIMARY KEY,
+            banned_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Per-user admin credentials; when empty the legacy ADMIN_PASSWORD is used
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS admins (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Migration: TOTP two-factor columns on admin accounts
+    let _ = conn.execute("ALTER TABLE admins ADD COLUMN totp_secret TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE admins ADD COLUMN totp_enabled INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE admins ADD COLUMN totp_last_step INTEGER", []);
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS admin_recovery_codes (
+            admin_id INTEGER NOT NULL REFERENCES admins (id) ON DELETE CASCADE,
+            code_hash TEXT NOT NULL,
+            used_at TEXT,
+            PRIMARY KEY (admin_id, code_hash)
+        )",
+        [],
+    )?;
+
+    // Browser login sessions, keyed by a hash of the cookie token
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS admin_sessions (
+            token_hash TEXT PRIMARY KEY,
+            username TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    // Migration: per-session CSRF token for admin mutations
+    let _ = conn.execute(
+        "ALTER TABLE admin_sessions ADD COLUMN csrf_token TEXT NOT NULL DEFAULT ''",
+        [],
+    );
+
+    // Record of every admin mutation and login attempt
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at TEXT NOT NULL,
+            admin TEXT NOT NULL,
+            ip_address TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT,
+            before TEXT,
+            after TEXT
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log (created_at)",
+        [],
+    )?;
+
+    // Values edited on the admin settings page, overriding the configured defaults
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+
     // Create cookie tracking table for soft limit (1 per 30 mins per device)
     conn.execute(
         "CREATE TABLE IF NOT EXISTS cookie_submissions (
@@ -84,10 +215,26 @@ pub fn init_database(db_path: &str) -> Result<Connection> {
         [&cutoff_str],
     );
 
+    // Clean up expired admin sessions
+    let now_str = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let _ = conn.execute(
+        "DELETE FROM admin_sessions WHERE expires_at < ?1",
+        [&now_str],
+    );
+
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
     log::info!("Database initialized at {db_path}");
     Ok(conn)
 }
 
+/// Submissions and blocked attempts from one IP within this window count towards
+/// the hard limit
+pub const IP_LIMIT_WINDOW_MINUTES: i64 = 60;
+/// A device may submit once within this window
+pub const COOKIE_LIMIT_WINDOW_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RateLimitType {
     CookieSoftLimit, // Same device, tried within 30 mins
     IpHardLimit,     // Same IP, 10+ submissions in last hour
@@ -100,7 +247,7 @@ pub fn check_rate_limits(
     ip_limit_max: i64,
 ) -> Result<Option<RateLimitType>> {
     // Check IP hard limit first (includes both actual submissions and blocked attempts)
-    let one_hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+    let one_hour_ago = chrono::Utc::now() - chrono::Duration::minutes(IP_LIMIT_WINDOW_MINUTES);
     let cutoff_str = one_hour_ago.format("%Y-%m-%d %H:%M:%S").to_string();
 
     // Count actual submissions
@@ -124,7 +271,8 @@ pub fn check_rate_limits(
     }
 
     // Check cookie soft limit (1 per 30 mins per device)
-    let thirty_mins_ago = chrono::Utc::now() - chrono::Duration::minutes(30);
+    let thirty_mins_ago =
+        chrono::Utc::now() - chrono::Duration::minutes(COOKIE_LIMIT_WINDOW_MINUTES);
     let cutoff_str = thirty_mins_ago.format("%Y-%m-%d %H:%M:%S").to_string();
 
     let cookie_count: i64 = conn.query_row(
@@ -157,3 +305,682 @@ pub fn record_ip_attempt(conn: &Connection, ip_address: &str) -> Result<()> {
     )?;
     Ok(())
 }
+
+const FEEDBACK_COLUMNS: &str = "id, character_name, server, is_anonymous, rating_mechanics,
+    rating_damage, rating_teamwork, rating_communication, rating_overall, comments,
+    content_type, player_job, ip_address, created_at, deleted_at, is_published,
+    (SELECT group_concat(tag, ',') FROM feedback_tags WHERE feedback_id = feedback.id), duty";
+
+fn feedback_from_row(row: &Row) -> Result<Feedback> {
+    Ok(Feedback {
+        id: row.get(0)?,
+        character_name: row.get(1)?,
+        server: row.get(2)?,
+        is_anonymous: row.get::<_, i32>(3)? != 0,
+        rating_mechanics: row.get(4)?,
+        rating_damage: row.get(5)?,
+        rating_teamwork: row.get(6)?,
+        rating_communication: row.get(7)?,
+        rating_overall: row.get(8)?,
+        comments: row.get(9)?,
+        content_type: row.get(10)?,
+        player_job: row.get(11)?,
+        duty: row.get(17)?,
+        ip_address: row.get(12)?,
+        created_at: row.get(13)?,
+        deleted_at: row.get(14)?,
+        is_published: row.get::<_, i32>(15)? != 0,
+        tags: row
+            .get::<_, Option<String>>(16)?
+            .map(|tags| tags.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+    })
+}
+
+/// Store a feedback entry with its tags. Returns false if one with the same id
+/// already exists, so imports can be re-run safely.
+pub fn insert_feedback(conn: &Connection, feedback: &Feedback) -> Result<bool> {
+    let rows = conn.execute(
+        "INSERT OR IGNORE INTO feedback (id, character_name, server, is_anonymous,
+         rating_mechanics, rating_damage, rating_teamwork, rating_communication,
+         rating_overall, comments, content_type, player_job, ip_address, created_at,
+         deleted_at, is_published, duty)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        rusqlite::params![
+            feedback.id,
+            feedback.character_name,
+            feedback.server,
+            feedback.is_anonymous as i32,
+            feedback.rating_mechanics,
+            feedback.rating_damage,
+            feedback.rating_teamwork,
+            feedback.rating_communication,
+            feedback.rating_overall,
+            feedback.comments,
+            feedback.content_type,
+            feedback.player_job,
+            feedback.ip_address,
+            feedback.created_at,
+            feedback.deleted_at,
+            feedback.is_published as i32,
+            feedback.duty,
+        ],
+    )?;
+    if rows == 0 {
+        return Ok(false);
+    }
+    for tag in &feedback.tags {
+        add_tag(conn, &feedback.id, tag)?;
+    }
+    Ok(true)
+}
+
+/// List live feedback, or the contents of the trash bin when `filter.trashed` is set,
+/// narrowed by any other filters that are set
+pub fn list_feedback(conn: &Connection, filter: &FeedbackFilter) -> Result<Vec<Feedback>> {
+    let sql = if filter.trashed {
+        format!(
+            "SELECT {FEEDBACK_COLUMNS} FROM feedback WHERE deleted_at IS NOT NULL
+               AND (?1 IS NULL OR is_published = ?1)
+               AND (?2 IS NULL OR id IN (SELECT feedback_id FROM feedback_tags WHERE tag = ?2))
+             ORDER BY deleted_at DESC"
+        )
+    } else {
+        format!(
+            "SELECT {FEEDBACK_COLUMNS} FROM feedback WHERE deleted_at IS NULL
+               AND (?1 IS NULL OR is_published = ?1)
+               AND (?2 IS NULL OR id IN (SELECT feedback_id FROM feedback_tags WHERE tag = ?2))
+             ORDER BY created_at DESC"
+        )
+    };
+
+    let mut stmt = conn.prepare(&sql)?;
+    let feedbacks = stmt
+        .query_map(
+            rusqlite::params![
+                filter.published.map(i32::from),
+                filter.tag.as_deref().filter(|v| !v.is_empty())
+            ],
+            feedback_from_row,
+        )?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(feedbacks)
+}
+
+pub fn get_feedback(conn: &Connection, id: &str) -> Result<Option<Feedback>> {
+    let sql = format!("SELECT {FEEDBACK_COLUMNS} FROM feedback WHERE id = ?1");
+    match conn.query_row(&sql, [id], feedback_from_row) {
+        Ok(feedback) => Ok(Some(feedback)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Move feedback to the trash bin. Returns false if no live entry matched.
+pub fn soft_delete_feedback(conn: &Connection, id: &str) -> Result<bool> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let rows = conn.execute(
+        "UPDATE feedback SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        rusqlite::params![now, id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Bring feedback back out of the trash bin. Returns false if it wasn't trashed.
+pub fn restore_feedback(conn: &Connection, id: &str) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE feedback SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        [id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Permanently delete feedback. Only entries already in the trash can be purged.
+pub fn purge_feedback(conn: &Connection, id: &str) -> Result<bool> {
+    let rows = conn.execute(
+        "DELETE FROM feedback WHERE id = ?1 AND deleted_at IS NOT NULL",
+        [id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Permanently delete everything that has been in the trash longer than `retention_days`
+pub fn purge_expired_trash(conn: &Connection, retention_days: i64) -> Result<usize> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days);
+    let cutoff_str = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "DELETE FROM feedback WHERE deleted_at IS NOT NULL AND deleted_at < ?1",
+        [&cutoff_str],
+    )
+}
+
+/// Returns false if the feedback doesn't exist
+pub fn set_published(conn: &Connection, id: &str, published: bool) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE feedback SET is_published = ?1 WHERE id = ?2",
+        rusqlite::params![published as i32, id],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Returns false if the feedback doesn't exist
+pub fn add_tag(conn: &Connection, id: &str, tag: &str) -> Result<bool> {
+    let exists: i64 =
+        conn.query_row("SELECT COUNT(*) FROM feedback WHERE id = ?1", [id], |row| {
+            row.get(0)
+        })?;
+    if exists == 0 {
+        return Ok(false);
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO feedback_tags (feedback_id, tag) VALUES (?1, ?2)",
+        rusqlite::params![id, tag],
+    )?;
+    Ok(true)
+}
+
+pub fn feedback_stats(conn: &Connection) -> Result<FeedbackStats> {
+    let (live, trashed, published, average_overall) = conn.query_row(
+        "SELECT
+            COALESCE(SUM(deleted_at IS NULL), 0),
+            COALESCE(SUM(deleted_at IS NOT NULL), 0),
+            COALESCE(SUM(deleted_at IS NULL AND is_published = 1), 0),
+            AVG(CASE WHEN deleted_at IS NULL THEN rating_overall END)
+         FROM feedback",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    let banned_ips = conn.query_row("SELECT COUNT(*) FROM banned_ips", [], |row| row.get(0))?;
+    let by_server = conn
+        .prepare(
+            "SELECT server, COUNT(*) FROM feedback
+             WHERE deleted_at IS NULL AND server IS NOT NULL
+             GROUP BY server",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+    let by_job = conn
+        .prepare(
+            "SELECT player_job, COUNT(*), SUM(rating_overall) FROM feedback
+             WHERE deleted_at IS NULL AND player_job IS NOT NULL
+             GROUP BY player_job",
+        )?
+        .query_map([], |row| {
+            Ok(RatingTally {
+                key: row.get(0)?,
+                count: row.get(1)?,
+                overall_total: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+    let by_duty = conn
+        .prepare(
+            "SELECT duty, COUNT(*), SUM(rating_overall) FROM feedback
+             WHERE deleted_at IS NULL AND duty IS NOT NULL
+             GROUP BY duty",
+        )?
+        .query_map([], |row| {
+            Ok(RatingTally {
+                key: row.get(0)?,
+                count: row.get(1)?,
+                overall_total: row.get(2)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+
+    Ok(FeedbackStats {
+        live,
+        trashed,
+        published,
+        banned_ips,
+        average_overall,
+        by_server,
+        by_job,
+        by_duty,
+    })
+}
+
+pub fn ban_ip(conn: &Connection, ip_address: &str) -> Result<()> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT OR IGNORE INTO banned_ips (ip_address, banned_at) VALUES (?1, ?2)",
+        rusqlite::params![ip_address, now],
+    )?;
+    Ok(())
+}
+
+pub fn is_ip_banned(conn: &Connection, ip_address: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM banned_ips WHERE ip_address = ?1",
+        [ip_address],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+pub fn record_audit(
+    conn: &Connection,
+    admin: &str,
+    ip_address: &str,
+    action: AuditAction,
+    target: Option<&str>,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> Result<()> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT INTO audit_log (created_at, admin, ip_address, action, target, before, after)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            now,
+            admin,
+            ip_address,
+            action.as_str(),
+            target,
+            before,
+            after
+        ],
+    )?;
+    Ok(())
+}
+
+/// Whether `admin` already has a successful login from `ip_address` within the window.
+/// Used to log one login per session rather than one per Basic-auth request.
+pub fn has_recent_login(
+    conn: &Connection,
+    admin: &str,
+    ip_address: &str,
+    window_minutes: i64,
+) -> Result<bool> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::minutes(window_minutes);
+    let cutoff_str = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM audit_log
+         WHERE admin = ?1 AND ip_address = ?2 AND action = ?3 AND created_at > ?4",
+        rusqlite::params![
+            admin,
+            ip_address,
+            AuditAction::LoginSuccess.as_str(),
+            cutoff_str
+        ],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Most recent audit entries first, narrowed by any filters that are set
+pub fn list_audit(conn: &Connection, filter: &AuditFilter, limit: i64) -> Result<Vec<AuditEntry>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, created_at, admin, ip_address, action, target, before, after
+         FROM audit_log
+         WHERE (?1 IS NULL OR action = ?1)
+           AND (?2 IS NULL OR admin LIKE '%' || ?2 || '%')
+           AND (?3 IS NULL OR target LIKE '%' || ?3 || '%' OR ip_address LIKE '%' || ?3 || '%')
+         ORDER BY id DESC
+         LIMIT ?4",
+    )?;
+
+    let entries = stmt
+        .query_map(
+            rusqlite::params![
+                filter.action.as_deref().filter(|v| !v.is_empty()),
+                filter.admin.as_deref().filter(|v| !v.is_empty()),
+                filter.search.as_deref().filter(|v| !v.is_empty()),
+                limit
+            ],
+            |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    admin: row.get(2)?,
+                    ip_address: row.get(3)?,
+                    action: row.get(4)?,
+                    target: row.get(5)?,
+                    before: row.get(6)?,
+                    after: row.get(7)?,
+                })
+            },
+        )?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(entries)
+}
+
+const ADMIN_COLUMNS: &str = "id, username, role, created_at, totp_enabled";
+
+fn admin_from_row(row: &Row) -> Result<AdminAccount> {
+    let role: String = row.get(2)?;
+    Ok(AdminAccount {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        // Unknown roles fail closed to the least privileged level
+        role: Role::parse(&role).unwrap_or(Role::Viewer),
+        created_at: row.get(3)?,
+        totp_enabled: row.get::<_, i32>(4)? != 0,
+    })
+}
+
+pub fn count_admins(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM admins", [], |row| row.get(0))
+}
+
+pub fn count_owners(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM admins WHERE role = ?1",
+        [Role::Owner.as_str()],
+        |row| row.get(0),
+    )
+}
+
+pub fn list_admins(conn: &Connection) -> Result<Vec<AdminAccount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ADMIN_COLUMNS} FROM admins ORDER BY username"
+    ))?;
+    let admins = stmt
+        .query_map([], admin_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(admins)
+}
+
+pub fn get_admin(conn: &Connection, id: i64) -> Result<Option<AdminAccount>> {
+    match conn.query_row(
+        &format!("SELECT {ADMIN_COLUMNS} FROM admins WHERE id = ?1"),
+        [id],
+        admin_from_row,
+    ) {
+        Ok(admin) => Ok(Some(admin)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the account and its password hash for login checks
+pub fn get_admin_credentials(
+    conn: &Connection,
+    username: &str,
+) -> Result<Option<(AdminAccount, String)>> {
+    match conn.query_row(
+        &format!("SELECT {ADMIN_COLUMNS}, password_hash FROM admins WHERE username = ?1"),
+        [username],
+        |row| Ok((admin_from_row(row)?, row.get(5)?)),
+    ) {
+        Ok(found) => Ok(Some(found)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn create_admin(
+    conn: &Connection,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+) -> Result<i64> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT INTO admins (username, password_hash, role, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![username, password_hash, role.as_str(), now],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn set_admin_role(conn: &Connection, id: i64, role: Role) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE admins SET role = ?1 WHERE id = ?2",
+        rusqlite::params![role.as_str(), id],
+    )?;
+    Ok(rows > 0)
+}
+
+pub fn delete_admin(conn: &Connection, id: i64) -> Result<bool> {
+    conn.execute(
+        "DELETE FROM admin_sessions WHERE username = (SELECT username FROM admins WHERE id = ?1)",
+        [id],
+    )?;
+    let rows = conn.execute("DELETE FROM admins WHERE id = ?1", [id])?;
+    Ok(rows > 0)
+}
+
+pub fn get_admin_by_username(conn: &Connection, username: &str) -> Result<Option<AdminAccount>> {
+    match conn.query_row(
+        &format!("SELECT {ADMIN_COLUMNS} FROM admins WHERE username = ?1"),
+        [username],
+        admin_from_row,
+    ) {
+        Ok(admin) => Ok(Some(admin)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the stored secret (pending or enabled) and the last accepted time step
+pub fn get_totp_secret(conn: &Connection, id: i64) -> Result<Option<(String, Option<i64>)>> {
+    conn.query_row(
+        "SELECT totp_secret, totp_last_step FROM admins WHERE id = ?1",
+        [id],
+        |row| {
+            let secret: Option<String> = row.get(0)?;
+            let last_step: Option<i64> = row.get(1)?;
+            Ok(secret.map(|s| (s, last_step)))
+        },
+    )
+}
+
+/// Store a new secret that isn't enforced until `enable_totp` confirms it
+pub fn set_pending_totp_secret(conn: &Connection, id: i64, secret: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE admins SET totp_secret = ?1, totp_enabled = 0, totp_last_step = NULL WHERE id = ?2",
+        rusqlite::params![secret, id],
+    )?;
+    Ok(())
+}
+
+/// Turn on TOTP and replace any existing recovery codes, all or nothing
+pub fn enable_totp(
+    conn: &Connection,
+    id: i64,
+    accepted_step: i64,
+    recovery_code_hashes: &[String],
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE admins SET totp_enabled = 1, totp_last_step = ?1 WHERE id = ?2",
+        rusqlite::params![accepted_step, id],
+    )?;
+    tx.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [id])?;
+    for hash in recovery_code_hashes {
+        tx.execute(
+            "INSERT INTO admin_recovery_codes (admin_id, code_hash) VALUES (?1, ?2)",
+            rusqlite::params![id, hash],
+        )?;
+    }
+    tx.commit()
+}
+
+pub fn disable_totp(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE admins SET totp_secret = NULL, totp_enabled = 0, totp_last_step = NULL
+         WHERE id = ?1",
+        [id],
+    )?;
+    tx.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [id])?;
+    tx.commit()
+}
+
+/// Remember the last accepted time step so a code can't be replayed
+pub fn record_totp_step(conn: &Connection, id: i64, step: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE admins SET totp_last_step = ?1 WHERE id = ?2",
+        rusqlite::params![step, id],
+    )?;
+    Ok(())
+}
+
+/// Mark a recovery code as used. Returns false if it was unknown or already used.
+pub fn use_recovery_code(conn: &Connection, id: i64, code_hash: &str) -> Result<bool> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let rows = conn.execute(
+        "UPDATE admin_recovery_codes SET used_at = ?1
+         WHERE admin_id = ?2 AND code_hash = ?3 AND used_at IS NULL",
+        rusqlite::params![now, id, code_hash],
+    )?;
+    Ok(rows > 0)
+}
+
+pub fn count_unused_recovery_codes(conn: &Connection, id: i64) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM admin_recovery_codes WHERE admin_id = ?1 AND used_at IS NULL",
+        [id],
+        |row| row.get(0),
+    )
+}
+
+pub fn create_session(
+    conn: &Connection,
+    token_hash: &str,
+    username: &str,
+    csrf_token: &str,
+    lifetime_hours: i64,
+) -> Result<()> {
+    let now = chrono::Utc::now();
+    let expires = now + chrono::Duration::hours(lifetime_hours);
+    conn.execute(
+        "INSERT INTO admin_sessions (token_hash, username, csrf_token, created_at, expires_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            token_hash,
+            username,
+            csrf_token,
+            now.format("%Y-%m-%d %H:%M:%S").to_string(),
+            expires.format("%Y-%m-%d %H:%M:%S").to_string()
+        ],
+    )?;
+    Ok(())
+}
+
+/// The username and CSRF token behind a session, if it exists and hasn't expired
+pub fn get_session(conn: &Connection, token_hash: &str) -> Result<Option<(String, String)>> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    match conn.query_row(
+        "SELECT username, csrf_token FROM admin_sessions
+         WHERE token_hash = ?1 AND expires_at > ?2",
+        rusqlite::params![token_hash, now],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ) {
+        Ok(session) => Ok(Some(session)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn delete_session(conn: &Connection, token_hash: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM admin_sessions WHERE token_hash = ?1",
+        [token_hash],
+    )?;
+    Ok(())
+}
+
+/// The player profile overrides saved from the settings page
+pub fn get_player_settings(conn: &Connection) -> Result<PlayerSettings> {
+    let mut stmt = conn.prepare("SELECT key, value FROM settings WHERE key LIKE 'player.%'")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    let mut settings = PlayerSettings::default();
+    for row in rows {
+        let (key, value) = row?;
+        let slot = match key.as_str() {
+            "player.name" => &mut settings.name,
+            "player.server" => &mut settings.server,
+            "player.datacenter" => &mut settings.datacenter,
+            "player.banner_image" => &mut settings.banner_image,
+            "player.profile_image" => &mut settings.profile_image,
+            "player.tagline" => &mut settings.tagline,
+            _ => continue,
+        };
+        *slot = Some(value);
+    }
+    Ok(settings)
+}
+
+/// Replace the player profile overrides. Unset fields are removed so they fall
+/// back to the configured defaults again.
+pub fn save_player_settings(conn: &Connection, settings: &PlayerSettings) -> Result<()> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let fields = [
+        ("player.name", &settings.name),
+        ("player.server", &settings.server),
+        ("player.datacenter", &settings.datacenter),
+        ("player.banner_image", &settings.banner_image),
+        ("player.profile_image", &settings.profile_image),
+        ("player.tagline", &settings.tagline),
+    ];
+
+    let tx = conn.unchecked_transaction()?;
+    for (key, value) in fields {
+        match value {
+            Some(value) => tx.execute(
+                "INSERT INTO settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+                rusqlite::params![key, value, now],
+            )?,
+            None => tx.execute("DELETE FROM settings WHERE key = ?1", [key])?,
+        };
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin_with_pending_totp() -> (Connection, i64) {
+        let conn = init_database(":memory:").unwrap();
+        let id = create_admin(&conn, "alice", "hash", Role::Owner).unwrap();
+        set_pending_totp_secret(&conn, id, "SECRET").unwrap();
+        (conn, id)
+    }
+
+    fn totp_enabled(conn: &Connection, id: i64) -> bool {
+        get_admin(conn, id).unwrap().unwrap().totp_enabled
+    }
+
+    #[test]
+    fn enable_totp_is_all_or_nothing() {
+        let (conn, id) = admin_with_pending_totp();
+        // The second insert breaks the primary key, so nothing may be kept
+        let hashes = ["a".to_string(), "a".to_string()];
+        assert!(enable_totp(&conn, id, 5, &hashes).is_err());
+        assert!(!totp_enabled(&conn, id));
+        assert_eq!(count_unused_recovery_codes(&conn, id).unwrap(), 0);
+        assert_eq!(
+            get_totp_secret(&conn, id).unwrap(),
+            Some(("SECRET".to_string(), None))
+        );
+
+        enable_totp(&conn, id, 5, &["a".to_string(), "b".to_string()]).unwrap();
+        assert!(totp_enabled(&conn, id));
+        assert_eq!(count_unused_recovery_codes(&conn, id).unwrap(), 2);
+
+        disable_totp(&conn, id).unwrap();
+        assert!(!totp_enabled(&conn, id));
+        assert_eq!(count_unused_recovery_codes(&conn, id).unwrap(), 0);
+        assert_eq!(get_totp_secret(&conn, id).unwrap(), None);
+    }
+
+    #[test]
+    fn unreadable_last_step_is_an_error() {
+        let (conn, id) = admin_with_pending_totp();
+        conn.execute(
+            "UPDATE admins SET totp_last_step = 'not a step' WHERE id = ?1",
+            [id],
+        )
+        .unwrap();
+        assert!(get_totp_secret(&conn, id).is_err());
+    }
+}
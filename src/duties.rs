@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use crate::models::{ContentType, RatingTally};
+
+/// The duty catalogue built into the binary
+const BUILTIN_DUTIES: &str = include_str!("../data/duties.toml");
+
+/// A duty players can pick on the form, e.g. one ultimate or one dungeon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duty {
+    pub name: String,
+    pub category: ContentType,
+    pub expansion: String,
+    /// Synced or minimum item level, where the duty has one
+    pub item_level: Option<u16>,
+}
+
+impl Duty {
+    /// Category and expansion, shown next to the name in the picker
+    pub fn label(&self) -> String {
+        match self.item_level {
+            Some(ilvl) => format!("{} · {} · i{ilvl}", self.category.as_str(), self.expansion),
+            None => format!("{} · {}", self.category.as_str(), self.expansion),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DutyEntry {
+    name: String,
+    category: String,
+    expansion: String,
+    item_level: Option<u16>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DutiesFile {
+    duties: Vec<DutyEntry>,
+}
+
+/// Every duty on the form's picker, in display order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DutyCatalogue {
+    duties: Vec<Duty>,
+}
+
+/// The built-in duty catalogue, parsed on first use
+pub fn catalogue() -> &'static DutyCatalogue {
+    static CATALOGUE: OnceLock<DutyCatalogue> = OnceLock::new();
+    CATALOGUE.get_or_init(|| {
+        DutyCatalogue::parse(BUILTIN_DUTIES).expect("the built-in duty catalogue is valid")
+    })
+}
+
+impl DutyCatalogue {
+    /// Parse a duty catalogue, rejecting unknown categories and duties listed twice
+    pub fn parse(contents: &str) -> Result<DutyCatalogue, String> {
+        let file: DutiesFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+
+        let mut names = HashSet::new();
+        let mut duties = Vec::with_capacity(file.duties.len());
+        for entry in file.duties {
+            if entry.name.trim().is_empty() || entry.expansion.trim().is_empty() {
+                return Err("every duty needs a name and an expansion".to_string());
+            }
+            let Some(category) = ContentType::parse(&entry.category) else {
+                return Err(format!(
+                    "duty {} has an unknown category {}",
+                    entry.name, entry.category
+                ));
+            };
+            if !names.insert(entry.name.to_lowercase()) {
+                return Err(format!("duty {} is listed twice", entry.name));
+            }
+            duties.push(Duty {
+                name: entry.name,
+                category,
+                expansion: entry.expansion,
+                item_level: entry.item_level,
+            });
+        }
+
+        Ok(DutyCatalogue { duties })
+    }
+
+    pub fn duties(&self) -> &[Duty] {
+        &self.duties
+    }
+
+    /// A duty by name, matched case-insensitively
+    pub fn find(&self, name: &str) -> Option<&Duty> {
+        let name = name.trim();
+        self.duties
+            .iter()
+            .find(|duty| duty.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Live feedback for one duty, see `by_duty`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DutyStats {
+    pub duty: String,
+    pub category: Option<ContentType>,
+    pub count: i64,
+    pub average_overall: f64,
+}
+
+/// Per-duty tallies with their catalogue category, lowest average rating first so
+/// the duties we're rated worst on lead the list
+pub fn by_duty<'a>(per_duty: impl IntoIterator<Item = &'a RatingTally>) -> Vec<DutyStats> {
+    let mut stats: Vec<DutyStats> = per_duty
+        .into_iter()
+        .filter(|tally| tally.count > 0)
+        .map(|tally| DutyStats {
+            duty: tally.key.clone(),
+            category: catalogue().find(&tally.key).map(|duty| duty.category),
+            count: tally.count,
+            average_overall: tally.average_overall(),
+        })
+        .collect();
+    stats.sort_by(|a, b| {
+        a.average_overall
+            .total_cmp(&b.average_overall)
+            .then_with(|| b.count.cmp(&a.count))
+    });
+    stats
+}
@@ -0,0 +1,197 @@
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use rinja::Template;
+use serde_json::json;
+
+use crate::pool::DbError;
+use crate::templates::ErrorTemplate;
+
+/// Sent with every 401, as RFC 7235 requires, so Basic-auth clients know to retry
+/// with credentials
+const AUTH_CHALLENGE: &str = "Basic realm=\"Admin Panel\"";
+
+/// Why a request failed. Handlers return it with `?`; the response is JSON unless
+/// the `error_pages` middleware finds the client wanted an HTML page.
+#[derive(Debug)]
+pub enum AppError {
+    Database(DbError),
+    Template(rinja::Error),
+    /// Any other failure on the server. The message is logged, never shown.
+    Internal(String),
+    /// The request was understood but its input was rejected
+    Validation(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::Database(e) => write!(f, "database error: {e}"),
+            AppError::Template(e) => write!(f, "template rendering failed: {e}"),
+            AppError::Internal(message)
+            | AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl AppError {
+    /// What the client is told. Server-side failures don't reveal their cause.
+    fn public_message(&self) -> &str {
+        match self {
+            AppError::Database(_) => "Database error",
+            AppError::Template(_) => "Template rendering failed",
+            AppError::Internal(_) => "Internal server error",
+            AppError::Validation(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message) => message,
+        }
+    }
+}
+
+impl From<DbError> for AppError {
+    fn from(e: DbError) -> Self {
+        AppError::Database(e)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        AppError::Database(DbError::Sqlite(e))
+    }
+}
+
+impl From<rinja::Error> for AppError {
+    fn from(e: rinja::Error) -> Self {
+        AppError::Template(e)
+    }
+}
+
+/// The details of an error response, kept on it so `error_pages` can render them
+#[derive(Debug, Clone)]
+struct ErrorReport {
+    message: String,
+    correlation_id: String,
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Database(_) | AppError::Template(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // Shown to the user so a report can be matched to this log line
+        let mut correlation_id = uuid::Uuid::new_v4().simple().to_string();
+        correlation_id.truncate(12);
+
+        let status = self.status_code();
+        if status.is_server_error() {
+            log::error!("[{correlation_id}] {self}");
+        } else {
+            log::info!("[{correlation_id}] {status}: {self}");
+        }
+
+        let report = ErrorReport {
+            message: self.public_message().to_string(),
+            correlation_id,
+        };
+        let mut builder = HttpResponse::build(status);
+        if status == StatusCode::UNAUTHORIZED {
+            builder.insert_header((header::WWW_AUTHENTICATE, AUTH_CHALLENGE));
+        }
+        let mut response = builder.json(json!({
+            "error": report.message,
+            "correlation_id": report.correlation_id,
+        }));
+        response.extensions_mut().insert(report);
+        response
+    }
+}
+
+/// Request bodies, paths and queries that fail to parse are validation errors
+pub fn extractor_error(err: impl std::fmt::Display, _: &HttpRequest) -> actix_web::Error {
+    AppError::Validation(err.to_string()).into()
+}
+
+fn wants_html(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Middleware turning `AppError` responses into an error page for browsers
+pub async fn error_pages(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    if !wants_html(&req) {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+    let back_url = if req.path().starts_with("/admin") {
+        "/admin/panel"
+    } else {
+        "/"
+    };
+
+    let res = next.call(req).await?;
+    let Some(report) = res.response().extensions().get::<ErrorReport>().cloned() else {
+        return Ok(res.map_into_left_body());
+    };
+    let status = res.status();
+    let template = ErrorTemplate {
+        status: status.as_u16(),
+        title: status.canonical_reason().unwrap_or("Error"),
+        message: report.message,
+        correlation_id: report.correlation_id,
+        back_url,
+    };
+    // Keep the JSON body if the page itself can't be rendered
+    let Ok(body) = template.render() else {
+        return Ok(res.map_into_left_body());
+    };
+    let mut page = HttpResponse::build(status);
+    if let Some(challenge) = res.headers().get(header::WWW_AUTHENTICATE) {
+        page.insert_header((header::WWW_AUTHENTICATE, challenge.clone()));
+    }
+    let page = page.content_type("text/html").body(body);
+    Ok(res.into_response(page).map_into_right_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unauthorized_responses_carry_a_challenge() {
+        let response = AppError::Unauthorized("Unauthorized".to_string()).error_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            AUTH_CHALLENGE
+        );
+
+        let response = AppError::Forbidden("Forbidden".to_string()).error_response();
+        assert!(response.headers().get(header::WWW_AUTHENTICATE).is_none());
+    }
+}
@@ -1,38 +1,110 @@
-use actix_web::{http::header, web, HttpRequest, HttpResponse};
-use parking_lot::Mutex;
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse};
 use rinja::Template;
 use rusqlite::Connection;
+use serde::Serialize;
 use serde_json::json;
-use std::sync::Arc;
+use std::path::PathBuf;
 
-use crate::db::{check_rate_limits, record_ip_attempt, record_submission, RateLimitType};
-use crate::models::{is_valid_server, Feedback, FeedbackSubmission};
+use crate::auth::{
+    constant_time_eq, dummy_password_hash, generate_recovery_codes, generate_token, hash_password,
+    hash_token, normalize_recovery_code, passwords_match, verify_password, CSRF_HEADER,
+    MIN_PASSWORD_LENGTH, SESSION_COOKIE, SESSION_HOURS,
+};
+use crate::backup;
+use crate::config::{Config, SharedSettings};
+use crate::db::{
+    self, count_admins, count_owners, count_unused_recovery_codes, create_admin, create_session,
+    delete_admin, delete_session, disable_totp, enable_totp, get_admin, get_admin_by_username,
+    get_admin_credentials, get_session, get_totp_secret, list_admins, record_totp_step,
+    save_player_settings, set_admin_role, set_pending_totp_secret, use_recovery_code,
+    RateLimitType,
+};
+use crate::duties;
+use crate::error::AppError;
+use crate::jobs::{self, Job};
+use crate::models::{
+    AdminAccount, AdminLoginForm, AuditAction, AuditFilter, BulkAction, BulkActionRequest,
+    BulkActionResult, CsrfForm, Feedback, FeedbackFilter, FeedbackSubmission, NewAdminRequest,
+    PlayerSettings, RatingTally, Role, TotpCodeRequest, UpdateAdminRoleRequest,
+};
+use crate::pool::{DbError, DbPool};
+use crate::proxy::{resolve_client_ip, TrustedProxies};
+use crate::repository::{FeedbackRepository, RepoResult};
+use crate::security::{self, CspNonce};
+use crate::store::FeedbackStore;
 use crate::templates::{
-    AdminLoginTemplate, AdminTemplate, DefaultPasswordErrorTemplate, IndexTemplate, PlayerConfig,
-    RateLimitedHardTemplate, RateLimitedTemplate, SuccessTemplate,
+    AdminAccountTemplate, AdminAuditTemplate, AdminLoginTemplate, AdminNav, AdminSettingsTemplate,
+    AdminTemplate, AdminTrashTemplate, AdminUsersTemplate, DefaultPasswordErrorTemplate,
+    IndexTemplate, RateLimitedHardTemplate, RateLimitedTemplate, SuccessTemplate,
 };
-
-pub type DbPool = Arc<Mutex<Connection>>;
+use crate::totp;
+use crate::uploads::{self, ImageKind};
+use crate::validation::{validate_feedback, FieldErrors};
+use crate::worlds::WorldRegistry;
 
 pub struct AppState {
+    /// Admin accounts, sessions and settings
     pub db: DbPool,
+    /// Feedback, bans, rate-limit counters and the audit log
+    pub store: FeedbackStore,
     pub admin_password: String,
-    pub discord_webhook_url: Option<String>,
-    pub player: PlayerConfig,
-    #[allow(dead_code)]
+    /// Player profile, filter words and notifier settings, swapped on reload
+    pub settings: SharedSettings,
     pub rate_limit_minutes: i64,
     pub ip_rate_limit_max: i64,
-    pub trusted_proxy_ips: Vec<String>,
+    pub trusted_proxies: TrustedProxies,
     pub is_default_admin_password: bool,
-    pub filter_words: Vec<String>,
+    pub trash_retention_days: i64,
+    pub backup_dir: PathBuf,
+    pub backup_keep: usize,
+    /// Optional directory whose `assets/` and `static/` files replace the embedded ones
+    pub assets_dir: Option<PathBuf>,
+    /// Where images uploaded from the settings page are written, served at /uploads
+    pub uploads_dir: PathBuf,
+}
+
+impl AppState {
+    /// Open the databases and load the settings saved from the admin panel
+    pub async fn open(config: &Config) -> Result<AppState, DbError> {
+        let db = DbPool::open(&config.database_path)?;
+        let store = {
+            let database_url = config.database_url.clone();
+            let sqlite = db.clone();
+            web::block(move || FeedbackStore::open(database_url.as_deref(), sqlite))
+                .await
+                .map_err(|e| DbError::Task(e.to_string()))??
+        };
+        log::info!("Feedback stored in {}", store.backend());
+
+        let player_overrides = match db.read(|conn| Ok(db::get_player_settings(conn)?)).await {
+            Ok(overrides) => overrides,
+            Err(e) => {
+                log::error!("Failed to load saved player settings, using defaults: {e}");
+                Default::default()
+            }
+        };
+
+        Ok(AppState {
+            db,
+            store,
+            admin_password: config.admin_password.clone(),
+            settings: SharedSettings::new(config.live_settings(player_overrides)),
+            rate_limit_minutes: config.rate_limit_minutes,
+            ip_rate_limit_max: config.ip_rate_limit_max,
+            trusted_proxies: config.trusted_proxies.clone(),
+            is_default_admin_password: config.is_default_admin_password,
+            trash_retention_days: config.trash_retention_days,
+            backup_dir: config.backup_dir.clone().into(),
+            backup_keep: config.backup_keep,
+            assets_dir: config.assets_dir.clone().map(Into::into),
+            uploads_dir: config.uploads_dir.clone().into(),
+        })
+    }
 }
 
-// Maximum allowed lengths for text fields to avoid unbounded DB growth
-const MAX_CHAR_NAME: usize = 100;
-const MAX_SERVER: usize = 50;
-const MAX_COMMENTS: usize = 200;
-const MAX_CONTENT_TYPE: usize = 100;
-const MAX_PLAYER_JOB: usize = 100;
+const MAX_TAG: usize = 30;
+const MAX_BULK_IDS: usize = 500;
 
 fn truncate_opt(input: Option<String>, max_chars: usize) -> Option<String> {
     input.and_then(|s| {
@@ -50,81 +122,63 @@ fn truncate_opt(input: Option<String>, max_chars: usize) -> Option<String> {
     })
 }
 
-/// Check if any field contains filter words (case-insensitive)
-fn contains_filter_words(text: Option<&str>, filter_words: &[String]) -> bool {
-    if let Some(text) = text {
-        let text_lower = text.to_lowercase();
-        filter_words.iter().any(|word| text_lower.contains(word))
-    } else {
-        false
-    }
+/// Returns the client IP used for storage and rate limiting.
+/// Forwarding headers are only honoured when the peer is a trusted proxy.
+fn get_client_ip(req: &HttpRequest, trusted_proxies: &TrustedProxies) -> String {
+    let peer_ip = req.peer_addr().map(|addr| addr.ip());
+    resolve_client_ip(peer_ip, req.headers(), trusted_proxies)
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string())
 }
 
-/// Check if feedback contains any filter words
-fn feedback_contains_filtered_words(form: &FeedbackSubmission, filter_words: &[String]) -> bool {
-    contains_filter_words(form.character_name.as_deref(), filter_words)
-        || contains_filter_words(form.server.as_deref(), filter_words)
-        || contains_filter_words(form.comments.as_deref(), filter_words)
-        || contains_filter_words(form.content_type.as_deref(), filter_words)
-        || contains_filter_words(form.player_job.as_deref(), filter_words)
+fn render_page(template: &impl Template) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/html")
+        .body(template.render()?))
 }
 
-/// Returns (peer_ip, display_ip)
-/// peer_ip: The actual connection source (always trusted, used for rate limiting)
-/// display_ip: Forwarded IP if from trusted proxy, otherwise peer_ip (for logging/Discord)
-fn get_client_ip(req: &HttpRequest, trusted_proxies: &[String]) -> (String, String) {
-    // Get the actual peer IP - this is the REAL connection source
-    let peer_ip = req
-        .connection_info()
-        .peer_addr()
-        .unwrap_or("unknown")
-        .to_string();
-
-    // Only trust forwarded headers if the peer IP is from a known proxy
-    let is_trusted_proxy = trusted_proxies.iter().any(|proxy| proxy.trim() == peer_ip);
-
-    let display_ip = if is_trusted_proxy {
-        // Safe to use forwarded header from this proxy
-        if let Some(forwarded) = req.headers().get("X-Forwarded-For") {
-            if let Ok(forwarded_str) = forwarded.to_str() {
-                if let Some(ip) = forwarded_str.split(',').next() {
-                    return (peer_ip, ip.trim().to_string());
-                }
-            }
-        }
-
-        if let Some(real_ip) = req.headers().get("X-Real-IP") {
-            if let Ok(ip) = real_ip.to_str() {
-                return (peer_ip, ip.trim().to_string());
-            }
-        }
-
-        peer_ip.clone()
+/// The feedback form, filled back in with the rejected values when `errors` is set
+fn feedback_form(
+    data: &AppState,
+    nonce: CspNonce,
+    form: FeedbackSubmission,
+    errors: FieldErrors,
+) -> Result<HttpResponse, AppError> {
+    let status = if errors.is_empty() {
+        StatusCode::OK
     } else {
-        // Not from a trusted proxy, use peer IP
-        peer_ip.clone()
+        StatusCode::BAD_REQUEST
     };
-
-    (peer_ip, display_ip)
-}
-
-pub async fn index(data: web::Data<AppState>) -> HttpResponse {
+    let settings = data.settings.load();
     let template = IndexTemplate {
-        player: data.player.clone(),
+        player: settings.player.clone(),
+        nonce: nonce.0,
+        worlds: settings.worlds.clone(),
+        form,
+        errors,
     };
-    match template.render() {
-        Ok(body) => HttpResponse::Ok().content_type("text/html").body(body),
-        Err(_) => HttpResponse::InternalServerError().body("Template rendering failed"),
-    }
+    Ok(HttpResponse::build(status)
+        .content_type("text/html")
+        .body(template.render()?))
+}
+
+pub async fn index(data: web::Data<AppState>, nonce: CspNonce) -> Result<HttpResponse, AppError> {
+    feedback_form(
+        &data,
+        nonce,
+        FeedbackSubmission::default(),
+        FieldErrors::default(),
+    )
 }
 
 pub async fn submit_feedback(
     req: HttpRequest,
     data: web::Data<AppState>,
+    nonce: CspNonce,
     form: web::Form<FeedbackSubmission>,
-) -> HttpResponse {
-    let (peer_ip, display_ip) = get_client_ip(&req, &data.trusted_proxy_ips);
-    let conn = data.db.lock();
+) -> Result<HttpResponse, AppError> {
+    let client_ip = get_client_ip(&req, &data.trusted_proxies);
+    let settings = data.settings.load();
 
     // Generate or retrieve cookie ID
     let cookie_id = if let Some(cookie) = req.cookie("feedback_session") {
@@ -133,206 +187,132 @@ pub async fn submit_feedback(
         uuid::Uuid::new_v4().to_string()
     };
 
-    // Always use peer_ip for rate limiting - can't be spoofed
-    // Never bypass rate limiting based on untrusted headers
-    match check_rate_limits(&conn, &peer_ip, &cookie_id, data.ip_rate_limit_max) {
-        Ok(Some(limit_type)) => {
-            match limit_type {
-                RateLimitType::CookieSoftLimit => {
-                    // Soft limit - same device, tried within 30 mins
-                    // Record this as an IP attempt to count towards the hard limit
-                    let _ = record_ip_attempt(&conn, &peer_ip);
-                    let template = RateLimitedTemplate {
-                        player: data.player.clone(),
-                    };
-                    match template.render() {
-                        Ok(body) => return HttpResponse::Ok().content_type("text/html").body(body),
-                        Err(_) => {
-                            return HttpResponse::InternalServerError()
-                                .body("Template rendering failed")
-                        }
-                    }
-                }
-                RateLimitType::IpHardLimit => {
-                    // Hard limit - too many submissions from this IP in the last hour
-                    let template = RateLimitedHardTemplate {
-                        player: data.player.clone(),
-                    };
-                    match template.render() {
-                        Ok(body) => return HttpResponse::Ok().content_type("text/html").body(body),
-                        Err(_) => {
-                            return HttpResponse::InternalServerError()
-                                .body("Template rendering failed")
-                        }
-                    }
-                }
-            }
+    // client_ip only differs from the peer address when a trusted proxy forwarded it,
+    // so untrusted headers can never be used to bypass rate limiting
+    let checks = data
+        .store
+        .read({
+            let client_ip = client_ip.clone();
+            let cookie_id = cookie_id.clone();
+            let ip_rate_limit_max = data.ip_rate_limit_max;
+            move |repo| check_submission(repo, &client_ip, &cookie_id, ip_rate_limit_max)
+        })
+        .await?;
+
+    match checks {
+        SubmissionCheck::Allowed => {}
+        SubmissionCheck::Banned => {
+            log::warn!("Rejected feedback submission from banned IP: {}", client_ip);
+            return render_page(&RateLimitedHardTemplate {
+                player: settings.player.clone(),
+            });
         }
-        Err(e) => {
-            log::error!("Rate limit check failed: {}", e);
-            return HttpResponse::InternalServerError().body("Database error");
+        SubmissionCheck::Limited(RateLimitType::CookieSoftLimit) => {
+            // Soft limit - same device, tried within 30 mins
+            // Record this as an IP attempt to count towards the hard limit
+            let ip = client_ip.clone();
+            let _ = data
+                .store
+                .write(move |repo| repo.record_ip_attempt(&ip))
+                .await;
+            return render_page(&RateLimitedTemplate {
+                player: settings.player.clone(),
+            });
         }
-        Ok(None) => {} // No limits hit, continue
-    }
-
-    // Check for filter words
-    if feedback_contains_filtered_words(&form, &data.filter_words) {
-        log::warn!(
-            "Feedback submission rejected due to filter words from IP: {}",
-            peer_ip
-        );
-        return HttpResponse::BadRequest().body("Submission contains prohibited content");
-    }
-
-    // Validate ratings
-    let ratings = [
-        form.rating_mechanics,
-        form.rating_damage,
-        form.rating_teamwork,
-        form.rating_communication,
-        form.rating_overall,
-    ];
-
-    for rating in ratings {
-        if !(1..=5).contains(&rating) {
-            return HttpResponse::BadRequest().body("Invalid rating value");
+        SubmissionCheck::Limited(RateLimitType::IpHardLimit) => {
+            // Hard limit - too many submissions from this IP in the last hour
+            return render_page(&RateLimitedHardTemplate {
+                player: settings.player.clone(),
+            });
         }
     }
 
-    // Validate server if provided and not anonymous
-    if !form.is_anonymous {
-        if let Some(ref server) = form.server {
-            if !server.is_empty() {
-                if server.chars().count() > MAX_SERVER {
-                    return HttpResponse::BadRequest().body("Invalid server name");
-                }
-                if !is_valid_server(server) {
-                    return HttpResponse::BadRequest().body("Invalid server name");
-                }
-            }
+    let validated = match validate_feedback(&form, &settings.filter_words, &settings.worlds) {
+        Ok(validated) => validated,
+        Err(errors) => {
+            log::warn!(
+                "Rejected feedback submission from IP {}: {}",
+                client_ip,
+                errors
+            );
+            return feedback_form(&data, nonce, form.into_inner(), errors);
         }
-    }
+    };
 
     let id = uuid::Uuid::new_v4().to_string();
     let created_at = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let feedback = validated.into_feedback(id, client_ip.clone(), created_at);
+    data.store
+        .write({
+            let cookie_id = cookie_id.clone();
+            let feedback = feedback.clone();
+            move |repo| store_submission(repo, &feedback, &cookie_id)
+        })
+        .await?;
+    log::info!("New feedback submitted from IP: {}", client_ip);
 
-    let (char_name, server) = if form.is_anonymous {
-        (None, None)
-    } else {
-        (
-            truncate_opt(form.character_name.clone(), MAX_CHAR_NAME),
-            truncate_opt(form.server.clone(), MAX_SERVER),
-        )
-    };
-
-    let comments = truncate_opt(form.comments.clone(), MAX_COMMENTS);
-    let content_type = truncate_opt(form.content_type.clone(), MAX_CONTENT_TYPE);
-    let player_job = truncate_opt(form.player_job.clone(), MAX_PLAYER_JOB);
-
-    let result = conn.execute(
-        "INSERT INTO feedback (id, character_name, server, is_anonymous, rating_mechanics, 
-         rating_damage, rating_teamwork, rating_communication, rating_overall, comments, 
-         content_type, player_job, ip_address, created_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
-        rusqlite::params![
-            id,
-            char_name.clone(),
-            server.clone(),
-            form.is_anonymous as i32,
-            form.rating_mechanics,
-            form.rating_damage,
-            form.rating_teamwork,
-            form.rating_communication,
-            form.rating_overall,
-            comments,
-            content_type,
-            player_job,
-            peer_ip,
-            created_at,
-        ],
-    );
-
-    match result {
-        Ok(_) => {
-            log::info!(
-                "New feedback submitted from IP: {} (displayed as {})",
-                peer_ip,
-                display_ip
-            );
-
-            // Send Discord notification if webhook is configured
-            if let Some(ref webhook_url) = data.discord_webhook_url {
-                let webhook_url = webhook_url.clone();
-                let feedback_data = DiscordFeedbackData {
-                    character_name: char_name,
-                    server,
-                    is_anonymous: form.is_anonymous,
-                    rating_mechanics: form.rating_mechanics,
-                    rating_damage: form.rating_damage,
-                    rating_teamwork: form.rating_teamwork,
-                    rating_communication: form.rating_communication,
-                    rating_overall: form.rating_overall,
-                    comments: comments.clone(),
-                    content_type: content_type.clone(),
-                    player_job: player_job.clone(),
-                };
-
-                // Spawn async task to send webhook (don't block response)
-                tokio::spawn(async move {
-                    if let Err(e) = send_discord_notification(&webhook_url, feedback_data).await {
-                        log::error!("Failed to send Discord notification: {}", e);
-                    }
-                });
+    // Send Discord notification if webhook is configured
+    if let Some(ref webhook_url) = settings.discord_webhook_url {
+        let webhook_url = webhook_url.clone();
+        // Spawn async task to send webhook (don't block response)
+        tokio::spawn(async move {
+            if let Err(e) = send_discord_notification(&webhook_url, feedback).await {
+                log::error!("Failed to send Discord notification: {}", e);
             }
+        });
+    }
 
-            // Record the cookie submission for soft limit tracking
-            if let Err(e) = record_submission(&conn, &cookie_id) {
-                log::error!("Failed to record cookie submission: {}", e);
-            }
+    let mut response = render_page(&SuccessTemplate {
+        player: settings.player.clone(),
+    })?;
 
-            let template = SuccessTemplate {
-                player: data.player.clone(),
-            };
-            let body = match template.render() {
-                Ok(b) => b,
-                Err(_) => {
-                    return HttpResponse::InternalServerError().body("Template rendering failed")
-                }
-            };
+    // Set cookie with 1 hour expiration
+    let cookie =
+        format!("feedback_session={cookie_id}; Max-Age=3600; Path=/; HttpOnly; SameSite=Lax");
+    if let Ok(header_value) = cookie.parse() {
+        response
+            .headers_mut()
+            .insert(header::SET_COOKIE, header_value);
+    }
 
-            let mut response = HttpResponse::Ok().content_type("text/html").body(body);
+    Ok(response)
+}
 
-            // Set cookie with 1 hour expiration
-            let cookie = format!(
-                "feedback_session={cookie_id}; Max-Age=3600; Path=/; HttpOnly; SameSite=Lax"
-            );
-            if let Ok(header_value) = cookie.parse() {
-                response
-                    .headers_mut()
-                    .insert(header::SET_COOKIE, header_value);
-            }
+/// Whether a submission may go ahead, checked before it is validated
+enum SubmissionCheck {
+    Banned,
+    Limited(RateLimitType),
+    Allowed,
+}
 
-            response
-        }
-        Err(e) => {
-            log::error!("Failed to insert feedback: {}", e);
-            HttpResponse::InternalServerError().body("Failed to save feedback")
-        }
+fn check_submission(
+    repo: &dyn FeedbackRepository,
+    ip_address: &str,
+    cookie_id: &str,
+    ip_rate_limit_max: i64,
+) -> RepoResult<SubmissionCheck> {
+    if repo.is_ip_banned(ip_address)? {
+        return Ok(SubmissionCheck::Banned);
     }
+    Ok(
+        match repo.check_rate_limits(ip_address, cookie_id, ip_rate_limit_max)? {
+            Some(limit_type) => SubmissionCheck::Limited(limit_type),
+            None => SubmissionCheck::Allowed,
+        },
+    )
 }
 
-struct DiscordFeedbackData {
-    character_name: Option<String>,
-    server: Option<String>,
-    is_anonymous: bool,
-    rating_mechanics: i32,
-    rating_damage: i32,
-    rating_teamwork: i32,
-    rating_communication: i32,
-    rating_overall: i32,
-    comments: Option<String>,
-    content_type: Option<String>,
-    player_job: Option<String>,
+fn store_submission(
+    repo: &dyn FeedbackRepository,
+    feedback: &Feedback,
+    cookie_id: &str,
+) -> RepoResult<()> {
+    repo.insert(feedback)?;
+    // Record the cookie submission for soft limit tracking
+    if let Err(e) = repo.record_submission(cookie_id) {
+        log::error!("Failed to record cookie submission: {}", e);
+    }
+    Ok(())
 }
 
 fn stars(rating: i32) -> String {
@@ -341,7 +321,7 @@ fn stars(rating: i32) -> String {
 
 async fn send_discord_notification(
     webhook_url: &str,
-    data: DiscordFeedbackData,
+    data: Feedback,
 ) -> Result<(), reqwest::Error> {
     let client = reqwest::Client::new();
 
@@ -359,11 +339,18 @@ async fn send_discord_notification(
     // Build context info
     let mut context_parts = Vec::new();
     if let Some(ref job) = data.player_job {
+        let job = match Job::parse(job) {
+            Some(known) => format!("{} ({})", known.name(), known.as_str()),
+            None => job.clone(),
+        };
         context_parts.push(format!("**Job:** {}", job));
     }
     if let Some(ref content) = data.content_type {
         context_parts.push(format!("**Content:** {}", content));
     }
+    if let Some(ref duty) = data.duty {
+        context_parts.push(format!("**Duty:** {}", duty));
+    }
     let context = if context_parts.is_empty() {
         "Not specified".to_string()
     } else {
@@ -441,97 +428,588 @@ async fn send_discord_notification(
     Ok(())
 }
 
-fn check_admin_auth(req: &HttpRequest, admin_password: &str) -> bool {
-    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if let Some(encoded) = auth_str.strip_prefix("Basic ") {
-                if let Ok(decoded) =
-                    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded)
-                {
-                    if let Ok(credentials) = String::from_utf8(decoded) {
-                        // Format: username:password
-                        if let Some((_user, pass)) = credentials.split_once(':') {
-                            return pass == admin_password;
-                        }
-                    }
-                }
+/// The authenticated admin making a request, recorded against audit log entries
+#[derive(Clone)]
+pub struct AdminIdentity {
+    pub username: String,
+    pub role: Role,
+    pub ip_address: String,
+    /// Token admin pages must echo back on mutations, empty for Basic auth
+    pub csrf_token: String,
+}
+
+// Repeat Basic-auth requests within this window count as the same login
+const LOGIN_SESSION_MINUTES: i64 = 30;
+
+const MAX_ADMIN_USERNAME: usize = 50;
+
+fn basic_auth_credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let auth_str = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = auth_str.strip_prefix("Basic ")?;
+    let decoded =
+        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded).ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    // Format: username:password
+    let (user, pass) = credentials.split_once(':')?;
+    Some((user.to_string(), pass.to_string()))
+}
+
+/// Who a set of credentials belongs to. `account` is None for the legacy shared
+/// ADMIN_PASSWORD login.
+struct Authenticated {
+    role: Role,
+    account: Option<AdminAccount>,
+}
+
+/// Look up who the credentials belong to. Once any admin accounts exist they are the
+/// only way in; until then the shared ADMIN_PASSWORD acts as an owner login.
+async fn authenticate(data: &AppState, username: &str, password: &str) -> Option<Authenticated> {
+    let username_owned = username.to_string();
+    let (admin_count, credentials) = data
+        .db
+        .read(move |conn| {
+            Ok((
+                count_admins(conn)?,
+                get_admin_credentials(conn, &username_owned)?,
+            ))
+        })
+        .await
+        .map_err(|e| log::error!("Failed to load admin credentials: {}", e))
+        .ok()?;
+
+    if admin_count == 0 {
+        return passwords_match(password, &data.admin_password).then_some(Authenticated {
+            role: Role::Owner,
+            account: None,
+        });
+    }
+
+    // Hashing is deliberately slow, so verify on a blocking thread. Unknown usernames
+    // still pay for a verification so response times don't reveal which ones exist.
+    let (account, hash) = match credentials {
+        Some((account, hash)) => (Some(account), hash),
+        None => (None, dummy_password_hash().to_string()),
+    };
+    let password = password.to_string();
+    let verified = web::block(move || verify_password(&password, &hash))
+        .await
+        .unwrap_or(false);
+    let account = account.filter(|_| verified)?;
+    Some(Authenticated {
+        role: account.role,
+        account: Some(account),
+    })
+}
+
+/// Check a second factor for an account with TOTP enabled. Accepts either a current
+/// authenticator code (each time step only once) or an unused recovery code.
+fn verify_second_factor(conn: &Connection, admin_id: i64, code: &str) -> rusqlite::Result<bool> {
+    if let Some((secret, last_step)) = get_totp_secret(conn, admin_id)? {
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        if let Some(step) = totp::verify(&secret, code, now) {
+            let step = step as i64;
+            if last_step.is_some_and(|last| step <= last) {
+                log::warn!("Rejected replayed TOTP code for admin {}", admin_id);
+                return Ok(false);
             }
+            record_totp_step(conn, admin_id, step)?;
+            return Ok(true);
+        }
+    }
+
+    use_recovery_code(conn, admin_id, &hash_token(&normalize_recovery_code(code)))
+}
+
+enum AuthError {
+    /// Missing or wrong credentials
+    Unauthorized,
+    /// Basic auth was used for an account that has two-factor enabled
+    TotpRequired,
+    /// Valid credentials, but the admin's role is too low
+    Forbidden,
+    /// A mutation without a valid CSRF token or from another origin
+    CrossSite,
+}
+
+impl From<AuthError> for AppError {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::Unauthorized => AppError::Unauthorized("Unauthorized".to_string()),
+            AuthError::TotpRequired => AppError::Unauthorized(
+                "Two-factor authentication is enabled for this account, sign in at /admin"
+                    .to_string(),
+            ),
+            AuthError::Forbidden => {
+                AppError::Forbidden("Your admin role does not allow this action".to_string())
+            }
+            AuthError::CrossSite => AppError::Forbidden(CROSS_SITE_REJECTED.to_string()),
         }
     }
-    false
 }
 
-pub async fn admin_login(data: web::Data<AppState>) -> HttpResponse {
-    if data.is_default_admin_password {
-        let template = DefaultPasswordErrorTemplate {};
-        match template.render() {
-            Ok(body) => return HttpResponse::Ok().content_type("text/html").body(body),
-            Err(_) => return HttpResponse::InternalServerError().body("Template rendering failed"),
+const CROSS_SITE_REJECTED: &str = "Cross-site request rejected";
+
+impl AuthError {
+    /// Browsers navigating to an admin page are sent to the login form instead
+    fn page_response(self) -> Result<HttpResponse, AppError> {
+        match self {
+            AuthError::Unauthorized | AuthError::TotpRequired => Ok(HttpResponse::SeeOther()
+                .insert_header((header::LOCATION, "/admin"))
+                .finish()),
+            AuthError::Forbidden | AuthError::CrossSite => Err(self.into()),
         }
     }
-    let template = AdminLoginTemplate {};
-    match template.render() {
-        Ok(body) => HttpResponse::Ok().content_type("text/html").body(body),
-        Err(_) => HttpResponse::InternalServerError().body("Template rendering failed"),
+}
+
+struct SessionAdmin {
+    username: String,
+    role: Role,
+    csrf_token: String,
+}
+
+/// Resolve a session cookie to the admin it belongs to. The role is looked up on
+/// every request so role changes and removed accounts take effect immediately.
+async fn session_admin(data: &AppState, token: &str) -> Option<SessionAdmin> {
+    let token_hash = hash_token(token);
+    data.db
+        .read(move |conn| {
+            let Some((username, csrf_token)) = get_session(conn, &token_hash)? else {
+                return Ok(None);
+            };
+            let role = match count_admins(conn)? {
+                0 => Some(Role::Owner),
+                _ => get_admin_by_username(conn, &username)?.map(|account| account.role),
+            };
+            Ok(role.map(|role| SessionAdmin {
+                username,
+                role,
+                csrf_token,
+            }))
+        })
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Failed to load admin session: {}", e);
+            None
+        })
+}
+
+/// Whether the request's Origin header (or failing that, its Referer) names this
+/// host. None when the client sent neither, as scripts usually don't.
+fn is_same_origin(req: &HttpRequest) -> Option<bool> {
+    let headers = req.headers();
+    let source = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))?;
+
+    // An opaque "null" origin or unparseable header never matches
+    let host = source
+        .to_str()
+        .ok()
+        .and_then(|value| value.split_once("://"))
+        .map(|(_, rest)| rest.split(['/', '?', '#']).next().unwrap_or_default());
+    Some(host.is_some_and(|host| host.eq_ignore_ascii_case(req.connection_info().host())))
+}
+
+fn is_mutation(req: &HttpRequest) -> bool {
+    !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Browser sessions must send the session's CSRF token and come from this site.
+fn check_csrf(req: &HttpRequest, session: &SessionAdmin) -> Result<(), AuthError> {
+    let provided = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let token_ok = !session.csrf_token.is_empty()
+        && constant_time_eq(provided.as_bytes(), session.csrf_token.as_bytes());
+
+    if !token_ok || is_same_origin(req) != Some(true) {
+        log::warn!(
+            "Rejected cross-site {} {} for admin '{}'",
+            req.method(),
+            req.path(),
+            session.username
+        );
+        return Err(AuthError::CrossSite);
+    }
+    Ok(())
+}
+
+async fn audit_login_failure(data: &AppState, username: &str, ip_address: &str) {
+    log::warn!(
+        "Failed admin login for '{}' from IP: {}",
+        username,
+        ip_address
+    );
+    let admin = AdminIdentity {
+        username: username.to_string(),
+        role: Role::Viewer,
+        ip_address: ip_address.to_string(),
+        csrf_token: String::new(),
+    };
+    audit_event::<()>(data, &admin, AuditAction::LoginFailure, None, None, None).await;
+}
+
+/// Authenticate with Basic auth, for scripts. Accounts with two-factor enabled
+/// have to sign in through the login form instead.
+async fn basic_auth_admin(
+    req: &HttpRequest,
+    data: &AppState,
+    ip_address: &str,
+) -> Result<(String, Role), AuthError> {
+    let (username, password) = basic_auth_credentials(req).ok_or(AuthError::Unauthorized)?;
+    let username = if username.is_empty() {
+        "admin".to_string()
+    } else {
+        username
+    };
+
+    let Some(authenticated) = authenticate(data, &username, &password).await else {
+        audit_login_failure(data, &username, ip_address).await;
+        return Err(AuthError::Unauthorized);
+    };
+    if authenticated.account.is_some_and(|a| a.totp_enabled) {
+        audit_login_failure(data, &username, ip_address).await;
+        return Err(AuthError::TotpRequired);
+    }
+
+    let admin = AdminIdentity {
+        username: username.clone(),
+        role: authenticated.role,
+        ip_address: ip_address.to_string(),
+        csrf_token: String::new(),
+    };
+    let result = data
+        .store
+        .write(move |repo| {
+            if !repo.has_recent_login(&admin.username, &admin.ip_address, LOGIN_SESSION_MINUTES)? {
+                audit::<()>(repo, &admin, AuditAction::LoginSuccess, None, None, None);
+            }
+            Ok(())
+        })
+        .await;
+    if let Err(e) = result {
+        log::error!("Failed to check recent logins: {}", e);
     }
+
+    Ok((username, authenticated.role))
 }
 
-pub async fn admin_panel(req: HttpRequest, data: web::Data<AppState>) -> HttpResponse {
-    if data.is_default_admin_password {
-        let template = DefaultPasswordErrorTemplate {};
-        match template.render() {
-            Ok(body) => return HttpResponse::Ok().content_type("text/html").body(body),
-            Err(_) => return HttpResponse::InternalServerError().body("Template rendering failed"),
+/// Authenticate the request and check the admin holds at least `required`.
+/// A login session cookie is preferred, with Basic auth as a fallback for scripts.
+async fn check_admin_auth(
+    req: &HttpRequest,
+    data: &AppState,
+    required: Role,
+) -> Result<AdminIdentity, AuthError> {
+    let ip_address = get_client_ip(req, &data.trusted_proxies);
+
+    let session = match req.cookie(SESSION_COOKIE) {
+        Some(cookie) => session_admin(data, cookie.value()).await,
+        None => None,
+    };
+    let admin = match session {
+        Some(session) => {
+            if is_mutation(req) {
+                check_csrf(req, &session)?;
+            }
+            AdminIdentity {
+                username: session.username,
+                role: session.role,
+                ip_address,
+                csrf_token: session.csrf_token,
+            }
+        }
+        None => {
+            // Scripts using Basic auth have no token, but a browser that cached the
+            // credentials still reveals a foreign origin
+            if is_mutation(req) && is_same_origin(req) == Some(false) {
+                log::warn!("Rejected cross-site {} {}", req.method(), req.path());
+                return Err(AuthError::CrossSite);
+            }
+            let (username, role) = basic_auth_admin(req, data, &ip_address).await?;
+            AdminIdentity {
+                username,
+                role,
+                ip_address,
+                csrf_token: String::new(),
+            }
         }
+    };
+
+    if !admin.role.allows(required) {
+        log::warn!(
+            "Admin '{}' ({}) denied access requiring {}",
+            admin.username,
+            admin.role.as_str(),
+            required.as_str()
+        );
+        return Err(AuthError::Forbidden);
     }
 
-    if !check_admin_auth(&req, &data.admin_password) {
-        return HttpResponse::Unauthorized()
-            .insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"Admin Panel\""))
-            .body("Unauthorized");
+    Ok(admin)
+}
+
+/// The default-password warning page, shown while the shared ADMIN_PASSWORD is
+/// still the default and no admin accounts have been created
+async fn default_password_page(
+    data: &AppState,
+    nonce: &CspNonce,
+) -> Option<Result<HttpResponse, AppError>> {
+    if !data.is_default_admin_password {
+        return None;
     }
+    match data.db.read(|conn| Ok(count_admins(conn)?)).await {
+        Ok(0) => {}
+        Ok(_) => return None,
+        Err(e) => log::error!("Failed to count admins: {}", e),
+    }
+
+    Some(render_page(&DefaultPasswordErrorTemplate {
+        nonce: nonce.0.clone(),
+    }))
+}
 
-    let conn = data.db.lock();
+fn snapshot<T: Serialize>(value: Option<&T>) -> Option<String> {
+    value.and_then(|v| serde_json::to_string(v).ok())
+}
 
-    let mut stmt = match conn.prepare(
-        "SELECT id, character_name, server, is_anonymous, rating_mechanics, rating_damage,
-         rating_teamwork, rating_communication, rating_overall, comments, content_type,
-         player_job, ip_address, created_at FROM feedback ORDER BY created_at DESC",
+/// Record an audit entry for use outside a transaction, where a logging
+/// failure shouldn't undo the action that was already taken
+fn audit<T: Serialize>(
+    repo: &dyn FeedbackRepository,
+    admin: &AdminIdentity,
+    action: AuditAction,
+    target: Option<&str>,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    if let Err(e) = repo.record_audit(
+        &admin.username,
+        &admin.ip_address,
+        action,
+        target,
+        snapshot(before).as_deref(),
+        snapshot(after).as_deref(),
     ) {
-        Ok(s) => s,
-        Err(e) => {
-            log::error!("Failed to prepare statement: {}", e);
-            return HttpResponse::InternalServerError().body("Database error");
-        }
-    };
-
-    let feedback_iter = stmt.query_map([], |row| {
-        Ok(Feedback {
-            id: row.get(0)?,
-            character_name: row.get(1)?,
-            server: row.get(2)?,
-            is_anonymous: row.get::<_, i32>(3)? != 0,
-            rating_mechanics: row.get(4)?,
-            rating_damage: row.get(5)?,
-            rating_teamwork: row.get(6)?,
-            rating_communication: row.get(7)?,
-            rating_overall: row.get(8)?,
-            comments: row.get(9)?,
-            content_type: row.get(10)?,
-            player_job: row.get(11)?,
-            ip_address: row.get(12)?,
-            created_at: row.get(13)?,
+        log::error!("Failed to write audit log entry: {}", e);
+    }
+}
+
+/// Record an audit entry for a change made outside the feedback store, such as to
+/// admin accounts or settings, once it has been applied
+async fn audit_event<T: Serialize>(
+    data: &AppState,
+    admin: &AdminIdentity,
+    action: AuditAction,
+    target: Option<&str>,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    let admin = admin.clone();
+    let target = target.map(str::to_string);
+    let (before, after) = (snapshot(before), snapshot(after));
+    let result = data
+        .store
+        .write(move |repo| {
+            repo.record_audit(
+                &admin.username,
+                &admin.ip_address,
+                action,
+                target.as_deref(),
+                before.as_deref(),
+                after.as_deref(),
+            )
         })
-    });
+        .await;
+    if let Err(e) = result {
+        log::error!("Failed to write audit log entry: {}", e);
+    }
+}
+
+fn render_login(
+    nonce: &CspNonce,
+    error: Option<&str>,
+    username: &str,
+    status: StatusCode,
+) -> Result<HttpResponse, AppError> {
+    let template = AdminLoginTemplate {
+        nonce: nonce.0.clone(),
+        error: error.map(str::to_string),
+        username: username.to_string(),
+    };
+    Ok(HttpResponse::build(status)
+        .content_type("text/html")
+        .body(template.render()?))
+}
+
+pub async fn admin_login(
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+    render_login(&nonce, None, "", StatusCode::OK)
+}
+
+pub async fn admin_login_submit(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+    form: web::Form<AdminLoginForm>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+
+    // Stop other sites from signing a browser into an attacker's account
+    if is_same_origin(&req) == Some(false) {
+        return Err(AppError::Forbidden(CROSS_SITE_REJECTED.to_string()));
+    }
+
+    let form = form.into_inner();
+    let username = match form.username.trim() {
+        "" => "admin".to_string(),
+        name => name.to_string(),
+    };
+    let ip_address = get_client_ip(&req, &data.trusted_proxies);
+
+    let Some(authenticated) = authenticate(&data, &username, &form.password).await else {
+        audit_login_failure(&data, &username, &ip_address).await;
+        return render_login(
+            &nonce,
+            Some("Invalid username or password"),
+            &username,
+            StatusCode::UNAUTHORIZED,
+        );
+    };
+
+    let totp_admin_id = authenticated
+        .account
+        .as_ref()
+        .filter(|a| a.totp_enabled)
+        .map(|a| a.id);
+    let code = form
+        .code
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string();
+    if totp_admin_id.is_some() && code.is_empty() {
+        return render_login(
+            &nonce,
+            Some("Enter the code from your authenticator app"),
+            &username,
+            StatusCode::UNAUTHORIZED,
+        );
+    }
+
+    let token = generate_token();
+    let admin = AdminIdentity {
+        username: username.clone(),
+        role: authenticated.role,
+        ip_address: ip_address.clone(),
+        csrf_token: generate_token(),
+    };
+    let token_hash = hash_token(&token);
+    let signed_in = data
+        .db
+        .write(move |conn| {
+            if let Some(admin_id) = totp_admin_id {
+                if !verify_second_factor(conn, admin_id, &code)? {
+                    return Ok(None);
+                }
+            }
+            create_session(
+                conn,
+                &token_hash,
+                &admin.username,
+                &admin.csrf_token,
+                SESSION_HOURS,
+            )?;
+            Ok(Some(admin))
+        })
+        .await?;
+
+    let Some(admin) = signed_in else {
+        audit_login_failure(&data, &username, &ip_address).await;
+        return render_login(
+            &nonce,
+            Some("Invalid authentication code"),
+            &username,
+            StatusCode::UNAUTHORIZED,
+        );
+    };
+    audit_event::<()>(&data, &admin, AuditAction::LoginSuccess, None, None, None).await;
+    log::info!("Admin '{}' signed in", admin.username);
+
+    let cookie = format!(
+        "{SESSION_COOKIE}={token}; Max-Age={}; Path=/admin; HttpOnly; SameSite=Strict",
+        SESSION_HOURS * 3600
+    );
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, "/admin/panel"))
+        .insert_header((header::SET_COOKIE, cookie))
+        .finish())
+}
 
-    let feedbacks: Vec<Feedback> = match feedback_iter {
-        Ok(iter) => iter.filter_map(|r| r.ok()).collect(),
-        Err(e) => {
-            log::error!("Failed to query feedback: {}", e);
-            return HttpResponse::InternalServerError().body("Database error");
+pub async fn admin_logout(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    form: web::Form<CsrfForm>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        let token_hash = hash_token(cookie.value());
+        let provided = form.into_inner().csrf_token;
+        let result = data
+            .db
+            .write(move |conn| {
+                if let Some((_, csrf_token)) = get_session(conn, &token_hash)? {
+                    if !constant_time_eq(provided.as_bytes(), csrf_token.as_bytes()) {
+                        return Ok(false);
+                    }
+                }
+                delete_session(conn, &token_hash)?;
+                Ok(true)
+            })
+            .await;
+        match result {
+            Ok(true) => {}
+            Ok(false) => return Err(AppError::Forbidden(CROSS_SITE_REJECTED.to_string())),
+            Err(e) => log::error!("Failed to delete admin session: {}", e),
         }
+    }
+
+    Ok(HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, "/admin"))
+        .insert_header((
+            header::SET_COOKIE,
+            format!("{SESSION_COOKIE}=; Max-Age=0; Path=/admin; HttpOnly; SameSite=Strict"),
+        ))
+        .finish())
+}
+
+pub async fn admin_panel(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+
+    let admin = match check_admin_auth(&req, &data, Role::Viewer).await {
+        Ok(admin) => admin,
+        Err(e) => return e.page_response(),
     };
 
+    let (feedbacks, trash_count) = data
+        .store
+        .read(|repo| Ok((repo.list(&FeedbackFilter::live())?, repo.stats()?.trashed)))
+        .await?;
+
     let total_count = feedbacks.len();
     let avg_overall: f32 = if total_count > 0 {
         feedbacks
@@ -543,45 +1021,1256 @@ pub async fn admin_panel(req: HttpRequest, data: web::Data<AppState>) -> HttpRes
         0.0
     };
 
-    let template = AdminTemplate {
-        player: data.player.clone(),
+    let settings = data.settings.load();
+    let by_datacenter = settings.worlds.count_by_datacenter(
+        feedbacks
+            .iter()
+            .filter_map(|f| f.server.as_deref())
+            .map(|server| (server, 1)),
+    );
+    let by_role = jobs::by_role(&RatingTally::tally(
+        feedbacks
+            .iter()
+            .filter_map(|f| Some((f.player_job.as_deref()?, f.rating_overall))),
+    ));
+    let by_duty = duties::by_duty(&RatingTally::tally(
+        feedbacks
+            .iter()
+            .filter_map(|f| Some((f.duty.as_deref()?, f.rating_overall))),
+    ));
+
+    render_page(&AdminTemplate {
+        nonce: nonce.0,
+        nav: AdminNav::new("panel", &admin),
+        player: settings.player.clone(),
         feedbacks,
         total_count,
         avg_overall,
+        trash_count,
+        worlds: settings.worlds.clone(),
+        by_datacenter,
+        by_role,
+        by_duty,
+    })
+}
+
+pub async fn delete_feedback(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Moderator).await?;
+
+    let id = path.into_inner();
+    let result = data
+        .store
+        .write({
+            let id = id.clone();
+            move |repo| apply_feedback_action(repo, &admin, BulkAction::Delete, &id, None)
+        })
+        .await?;
+
+    match result {
+        Ok(_) => {
+            log::info!("Moved feedback to trash: {}", id);
+            Ok(HttpResponse::Ok().body("Deleted"))
+        }
+        Err(_) => Err(AppError::NotFound("Feedback not found".to_string())),
+    }
+}
+
+pub async fn admin_trash(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+
+    let admin = match check_admin_auth(&req, &data, Role::Moderator).await {
+        Ok(admin) => admin,
+        Err(e) => return e.page_response(),
     };
 
-    match template.render() {
-        Ok(body) => HttpResponse::Ok().content_type("text/html").body(body),
-        Err(_) => HttpResponse::InternalServerError().body("Template rendering failed"),
+    let feedbacks = data
+        .store
+        .read(|repo| repo.list(&FeedbackFilter::trash()))
+        .await?;
+
+    render_page(&AdminTrashTemplate {
+        nonce: nonce.0,
+        nav: AdminNav::new("trash", &admin),
+        player: data.settings.load().player.clone(),
+        feedbacks,
+        retention_days: data.trash_retention_days,
+    })
+}
+
+/// Run a trash-bin operation on one entry and audit it, in the transaction `repo` is
+/// part of. Returns false if the operation didn't match anything.
+fn record_trash_change(
+    repo: &dyn FeedbackRepository,
+    admin: &AdminIdentity,
+    action: AuditAction,
+    id: &str,
+    change: fn(&dyn FeedbackRepository, &str) -> RepoResult<bool>,
+) -> RepoResult<bool> {
+    let before = repo.get(id)?;
+    if !change(repo, id)? {
+        return Ok(false);
     }
+    let after = repo.get(id)?;
+    repo.record_audit(
+        &admin.username,
+        &admin.ip_address,
+        action,
+        Some(id),
+        snapshot(before.as_ref()).as_deref(),
+        snapshot(after.as_ref()).as_deref(),
+    )?;
+    Ok(true)
 }
 
-pub async fn delete_feedback(
+pub async fn restore_deleted_feedback(
     req: HttpRequest,
     data: web::Data<AppState>,
     path: web::Path<String>,
-) -> HttpResponse {
-    if !check_admin_auth(&req, &data.admin_password) {
-        return HttpResponse::Unauthorized()
-            .insert_header((header::WWW_AUTHENTICATE, "Basic realm=\"Admin Panel\""))
-            .body("Unauthorized");
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Moderator).await?;
+
+    let id = path.into_inner();
+    let result = data
+        .store
+        .write({
+            let id = id.clone();
+            move |repo| {
+                record_trash_change(repo, &admin, AuditAction::Restore, &id, |repo, id| {
+                    repo.restore(id)
+                })
+            }
+        })
+        .await?;
+
+    if !result {
+        return Err(AppError::NotFound(
+            "Feedback not found in trash".to_string(),
+        ));
     }
+    log::info!("Restored feedback from trash: {}", id);
+    Ok(HttpResponse::Ok().body("Restored"))
+}
+
+pub async fn purge_deleted_feedback(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Moderator).await?;
 
     let id = path.into_inner();
-    let conn = data.db.lock();
-
-    match conn.execute("DELETE FROM feedback WHERE id = ?1", [&id]) {
-        Ok(rows) => {
-            if rows > 0 {
-                log::info!("Deleted feedback: {}", id);
-                HttpResponse::Ok().body("Deleted")
-            } else {
-                HttpResponse::NotFound().body("Feedback not found")
+    let result = data
+        .store
+        .write({
+            let id = id.clone();
+            move |repo| {
+                record_trash_change(repo, &admin, AuditAction::Purge, &id, |repo, id| {
+                    repo.purge(id)
+                })
             }
+        })
+        .await?;
+
+    if !result {
+        return Err(AppError::NotFound(
+            "Feedback not found in trash".to_string(),
+        ));
+    }
+    log::info!("Permanently deleted feedback: {}", id);
+    Ok(HttpResponse::Ok().body("Purged"))
+}
+
+/// Apply one admin action to one feedback entry and audit it.
+/// `Ok(Err(..))` is a per-id failure; `Err(..)` aborts the whole batch.
+fn apply_feedback_action(
+    repo: &dyn FeedbackRepository,
+    admin: &AdminIdentity,
+    action: BulkAction,
+    id: &str,
+    tag: Option<&str>,
+) -> RepoResult<Result<Option<Feedback>, String>> {
+    let Some(before) = repo.get(id)? else {
+        return Ok(Err("Feedback not found".to_string()));
+    };
+    // Trashed entries can still be exported, everything else waits for a restore
+    if before.deleted_at.is_some() && action != BulkAction::Export {
+        return Ok(Err("Feedback is in the trash".to_string()));
+    }
+
+    match action {
+        BulkAction::Delete => {
+            repo.soft_delete(id)?;
+        }
+        BulkAction::Publish => {
+            repo.set_published(id, true)?;
+        }
+        BulkAction::Unpublish => {
+            repo.set_published(id, false)?;
+        }
+        BulkAction::Tag => {
+            repo.add_tag(id, tag.unwrap_or_default())?;
+        }
+        BulkAction::Ban => {
+            repo.ban_ip(&before.ip_address)?;
+        }
+        BulkAction::Export => {
+            repo.record_audit(
+                &admin.username,
+                &admin.ip_address,
+                AuditAction::Export,
+                Some(id),
+                None,
+                None,
+            )?;
+            return Ok(Ok(Some(before)));
+        }
+    }
+
+    let after = repo.get(id)?;
+    repo.record_audit(
+        &admin.username,
+        &admin.ip_address,
+        action.into(),
+        Some(id),
+        snapshot(Some(&before)).as_deref(),
+        snapshot(after.as_ref()).as_deref(),
+    )?;
+
+    Ok(Ok(None))
+}
+
+pub async fn bulk_action(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<BulkActionRequest>,
+) -> Result<HttpResponse, AppError> {
+    let request = body.into_inner();
+
+    let admin = check_admin_auth(&req, &data, request.action.required_role()).await?;
+
+    if request.ids.is_empty() || request.ids.len() > MAX_BULK_IDS {
+        return Err(AppError::Validation("Invalid number of ids".to_string()));
+    }
+
+    let tag = if request.action == BulkAction::Tag {
+        match truncate_opt(request.tag, MAX_TAG) {
+            Some(tag) if !tag.contains(',') => Some(tag),
+            _ => return Err(AppError::Validation("Invalid tag".to_string())),
         }
-        Err(e) => {
-            log::error!("Failed to delete feedback: {}", e);
-            HttpResponse::InternalServerError().body("Failed to delete")
-        }
+    } else {
+        None
+    };
+
+    let action = request.action;
+    let username = admin.username.clone();
+    let results = data
+        .store
+        .write(move |repo| {
+            let mut results = Vec::with_capacity(request.ids.len());
+            for id in request.ids {
+                // Returning an error rolls back the transaction, undoing everything so far
+                let outcome = apply_feedback_action(repo, &admin, action, &id, tag.as_deref())
+                    .inspect_err(|e| log::error!("Bulk {:?} failed on {}: {}", action, id, e))?;
+                results.push(match outcome {
+                    Ok(feedback) => BulkActionResult {
+                        id,
+                        ok: true,
+                        error: None,
+                        feedback,
+                    },
+                    Err(error) => BulkActionResult {
+                        id,
+                        ok: false,
+                        error: Some(error),
+                        feedback: None,
+                    },
+                });
+            }
+            Ok(results)
+        })
+        .await?;
+
+    let succeeded = results.iter().filter(|r| r.ok).count();
+    log::info!(
+        "Bulk {:?} by {} applied to {}/{} feedback entries",
+        action,
+        username,
+        succeeded,
+        results.len()
+    );
+
+    Ok(HttpResponse::Ok().json(json!({ "results": results })))
+}
+
+// Upper bound on rows rendered in the audit log page
+const AUDIT_PAGE_LIMIT: i64 = 500;
+
+pub async fn admin_audit(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+    query: web::Query<AuditFilter>,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+
+    let admin = match check_admin_auth(&req, &data, Role::Owner).await {
+        Ok(admin) => admin,
+        Err(e) => return e.page_response(),
+    };
+
+    let filter = query.into_inner();
+    let entries = data
+        .store
+        .read({
+            let filter = filter.clone();
+            move |repo| repo.list_audit(&filter, AUDIT_PAGE_LIMIT)
+        })
+        .await?;
+
+    render_page(&AdminAuditTemplate {
+        nav: AdminNav::new("audit", &admin),
+        player: data.settings.load().player.clone(),
+        entries,
+        actions: AuditAction::ALL.iter().map(|a| a.as_str()).collect(),
+        filter_action: filter.action.unwrap_or_default(),
+        filter_admin: filter.admin.unwrap_or_default(),
+        filter_search: filter.search.unwrap_or_default(),
+    })
+}
+
+pub async fn admin_users(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+
+    let admin = match check_admin_auth(&req, &data, Role::Owner).await {
+        Ok(admin) => admin,
+        Err(e) => return e.page_response(),
+    };
+
+    let admins = data.db.read(|conn| Ok(list_admins(conn)?)).await?;
+
+    render_page(&AdminUsersTemplate {
+        nonce: nonce.0,
+        nav: AdminNav::new("users", &admin),
+        player: data.settings.load().player.clone(),
+        legacy_login: admins.is_empty(),
+        admins,
+        roles: Role::ALL.iter().map(|r| r.as_str()).collect(),
+        min_password_length: MIN_PASSWORD_LENGTH,
+    })
+}
+
+fn is_valid_admin_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.chars().count() <= MAX_ADMIN_USERNAME
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+pub async fn create_admin_account(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<NewAdminRequest>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Owner).await?;
+
+    let request = body.into_inner();
+    let username = request.username.trim();
+
+    if !is_valid_admin_username(username) {
+        return Err(AppError::Validation(
+            "Usernames may only contain letters, numbers, '.', '_' and '-'".to_string(),
+        ));
+    }
+    if request.password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::Validation(format!(
+            "Passwords must be at least {MIN_PASSWORD_LENGTH} characters"
+        )));
+    }
+
+    // Hashing is deliberately slow, so keep it off the request workers
+    let password = request.password.clone();
+    let password_hash = web::block(move || hash_password(&password))
+        .await
+        .map_err(|e| AppError::Internal(format!("Password hashing task failed: {e}")))?
+        .map_err(|e| AppError::Internal(format!("Failed to hash password: {e}")))?;
+
+    let username = username.to_string();
+    let role = request.role;
+    let result = data
+        .db
+        .write({
+            let username = username.clone();
+            move |conn| {
+                // The first account replaces the shared password, so it must be able to
+                // manage the rest
+                if count_admins(conn)? == 0 && role != Role::Owner {
+                    return Ok(None);
+                }
+                let id = create_admin(conn, &username, &password_hash, role)?;
+                Ok(Some(get_admin(conn, id).ok().flatten()))
+            }
+        })
+        .await;
+
+    let created = match result {
+        Ok(Some(created)) => created,
+        Ok(None) => {
+            return Err(AppError::Validation(
+                "The first admin account must be an owner".to_string(),
+            ))
+        }
+        Err(DbError::Sqlite(rusqlite::Error::SqliteFailure(e, _)))
+            if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+        {
+            return Err(AppError::Conflict(
+                "An admin with that username already exists".to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    audit_event(
+        &data,
+        &admin,
+        AuditAction::AdminCreate,
+        Some(&username),
+        None,
+        created.as_ref(),
+    )
+    .await;
+    log::info!(
+        "Admin '{}' created {} account '{}'",
+        admin.username,
+        role.as_str(),
+        username
+    );
+    Ok(HttpResponse::Ok().json(created))
+}
+
+/// The outcome of changing or removing an admin account
+enum AdminChange {
+    /// Applied, with the account as it was before and after
+    Done(AdminAccount, Option<AdminAccount>),
+    NotFound,
+    /// Refused because it would leave no owner
+    LastOwner,
+}
+
+pub async fn update_admin_role(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    body: web::Json<UpdateAdminRoleRequest>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Owner).await?;
+
+    let id = path.into_inner();
+    let role = body.into_inner().role;
+    let result = data
+        .db
+        .write(move |conn| {
+            let Some(before) = get_admin(conn, id)? else {
+                return Ok(AdminChange::NotFound);
+            };
+            if before.role == Role::Owner && role != Role::Owner && count_owners(conn)? <= 1 {
+                return Ok(AdminChange::LastOwner);
+            }
+            set_admin_role(conn, id, role)?;
+            let after = get_admin(conn, id).ok().flatten();
+            Ok(AdminChange::Done(before, after))
+        })
+        .await?;
+
+    let (before, after) = match result {
+        AdminChange::Done(before, after) => (before, after),
+        AdminChange::NotFound => return Err(AppError::NotFound("Admin not found".to_string())),
+        AdminChange::LastOwner => {
+            return Err(AppError::Validation(
+                "Cannot demote the last owner".to_string(),
+            ))
+        }
+    };
+
+    audit_event(
+        &data,
+        &admin,
+        AuditAction::AdminUpdate,
+        Some(&before.username),
+        Some(&before),
+        after.as_ref(),
+    )
+    .await;
+    log::info!(
+        "Admin '{}' changed role of '{}' to {}",
+        admin.username,
+        before.username,
+        role.as_str()
+    );
+    Ok(HttpResponse::Ok().body("Updated"))
+}
+
+pub async fn delete_admin_account(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Owner).await?;
+
+    let id = path.into_inner();
+    let result = data
+        .db
+        .write(move |conn| {
+            let Some(before) = get_admin(conn, id)? else {
+                return Ok(AdminChange::NotFound);
+            };
+            if before.role == Role::Owner && count_owners(conn)? <= 1 {
+                return Ok(AdminChange::LastOwner);
+            }
+            delete_admin(conn, id)?;
+            Ok(AdminChange::Done(before, None))
+        })
+        .await?;
+
+    let before = match result {
+        AdminChange::Done(before, _) => before,
+        AdminChange::NotFound => return Err(AppError::NotFound("Admin not found".to_string())),
+        AdminChange::LastOwner => {
+            return Err(AppError::Validation(
+                "Cannot delete the last owner".to_string(),
+            ))
+        }
+    };
+
+    audit_event(
+        &data,
+        &admin,
+        AuditAction::AdminDelete,
+        Some(&before.username),
+        Some(&before),
+        None,
+    )
+    .await;
+    log::info!(
+        "Admin '{}' deleted account '{}'",
+        admin.username,
+        before.username
+    );
+    Ok(HttpResponse::Ok().body("Deleted"))
+}
+
+const TOTP_ISSUER: &str = "FinalFeedback";
+const INVALID_CODE: &str = "Invalid authentication code";
+
+pub async fn admin_account(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+) -> Result<HttpResponse, AppError> {
+    let admin = match check_admin_auth(&req, &data, Role::Viewer).await {
+        Ok(admin) => admin,
+        Err(e) => return e.page_response(),
+    };
+
+    let username = admin.username.clone();
+    let (account, recovery_codes_left) = data
+        .db
+        .read(move |conn| {
+            let account = get_admin_by_username(conn, &username)?;
+            let recovery_codes_left = match &account {
+                Some(account) if account.totp_enabled => {
+                    count_unused_recovery_codes(conn, account.id).unwrap_or(0)
+                }
+                _ => 0,
+            };
+            Ok((account, recovery_codes_left))
+        })
+        .await?;
+
+    render_page(&AdminAccountTemplate {
+        nonce: nonce.0,
+        nav: AdminNav::new("account", &admin),
+        player: data.settings.load().player.clone(),
+        account,
+        recovery_codes_left,
+    })
+}
+
+#[derive(Serialize)]
+struct TotpSetup {
+    secret: String,
+    uri: String,
+    qr_svg: Option<String>,
+}
+
+/// Generate a new secret for the signed-in admin. It isn't enforced until a
+/// code from it has been confirmed through `enable_totp_for_account`.
+pub async fn setup_totp(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Viewer).await?;
+
+    let username = admin.username.clone();
+    let Some(account) = data
+        .db
+        .read(move |conn| Ok(get_admin_by_username(conn, &username)?))
+        .await?
+    else {
+        return Err(AppError::Validation(
+            "Two-factor authentication needs a personal admin account".to_string(),
+        ));
+    };
+    if account.totp_enabled {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+
+    let secret = totp::generate_secret();
+    data.db
+        .write({
+            let secret = secret.clone();
+            let admin_id = account.id;
+            move |conn| Ok(set_pending_totp_secret(conn, admin_id, &secret)?)
+        })
+        .await?;
+
+    let uri = totp::provisioning_uri(&secret, TOTP_ISSUER, &account.username);
+    Ok(HttpResponse::Ok().json(TotpSetup {
+        qr_svg: totp::qr_code_svg(&uri),
+        secret,
+        uri,
+    }))
+}
+
+pub async fn enable_totp_for_account(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<TotpCodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Viewer).await?;
+
+    let username = admin.username.clone();
+    let pending = data
+        .db
+        .read(move |conn| {
+            let Some(account) =
+                get_admin_by_username(conn, &username)?.filter(|account| !account.totp_enabled)
+            else {
+                return Ok(None);
+            };
+            Ok(get_totp_secret(conn, account.id)?.map(|(secret, _)| (account, secret)))
+        })
+        .await?;
+    let Some((account, secret)) = pending else {
+        return Err(AppError::Validation(
+            "No two-factor setup is pending".to_string(),
+        ));
+    };
+
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    let Some(step) = totp::verify(&secret, &body.code, now) else {
+        return Err(AppError::Validation(INVALID_CODE.to_string()));
+    };
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+    data.db
+        .write(move |conn| Ok(enable_totp(conn, account.id, step as i64, &hashes)?))
+        .await?;
+    audit_event::<()>(
+        &data,
+        &admin,
+        AuditAction::TotpEnable,
+        Some(&account.username),
+        None,
+        None,
+    )
+    .await;
+    log::info!(
+        "Admin '{}' enabled two-factor authentication",
+        admin.username
+    );
+
+    Ok(HttpResponse::Ok().json(json!({ "recovery_codes": recovery_codes })))
+}
+
+pub async fn disable_totp_for_account(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<TotpCodeRequest>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Viewer).await?;
+
+    let username = admin.username.clone();
+    let Some(account) = data
+        .db
+        .read(move |conn| Ok(get_admin_by_username(conn, &username)?))
+        .await?
+        .filter(|account| account.totp_enabled)
+    else {
+        return Err(AppError::Validation(
+            "Two-factor authentication is not enabled".to_string(),
+        ));
+    };
+
+    let code = body.into_inner().code;
+    let disabled = data
+        .db
+        .write({
+            let account_id = account.id;
+            move |conn| {
+                // Require a current code so a hijacked session can't quietly remove the
+                // second factor
+                if !verify_second_factor(conn, account_id, &code)? {
+                    return Ok(false);
+                }
+                disable_totp(conn, account_id)?;
+                Ok(true)
+            }
+        })
+        .await?;
+    if !disabled {
+        return Err(AppError::Validation(INVALID_CODE.to_string()));
+    }
+    audit_event::<()>(
+        &data,
+        &admin,
+        AuditAction::TotpDisable,
+        Some(&account.username),
+        None,
+        None,
+    )
+    .await;
+
+    log::info!(
+        "Admin '{}' disabled two-factor authentication",
+        admin.username
+    );
+
+    Ok(HttpResponse::Ok().body("Disabled"))
+}
+
+// Player settings
+
+const MAX_PLAYER_NAME_LENGTH: usize = 50;
+const MAX_TAGLINE_LENGTH: usize = 200;
+const MAX_IMAGE_URL_LENGTH: usize = 500;
+
+pub async fn admin_settings(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    nonce: CspNonce,
+) -> Result<HttpResponse, AppError> {
+    if let Some(page) = default_password_page(&data, &nonce).await {
+        return page;
+    }
+
+    let admin = match check_admin_auth(&req, &data, Role::Owner).await {
+        Ok(admin) => admin,
+        Err(e) => return e.page_response(),
+    };
+
+    let backups = backup::list_backups(&data.backup_dir).unwrap_or_else(|e| {
+        log::error!("Failed to list backups: {}", e);
+        Vec::new()
+    });
+
+    let settings = data.settings.load();
+    render_page(&AdminSettingsTemplate {
+        nonce: nonce.0,
+        nav: AdminNav::new("settings", &admin),
+        player: settings.player.clone(),
+        defaults: settings.player_defaults.clone(),
+        overrides: settings.player_overrides.clone(),
+        worlds: settings.worlds.clone(),
+        backups,
+        backup_keep: data.backup_keep,
+    })
+}
+
+/// Trim a submitted value, treating a blank one as "use the default"
+fn clean_setting(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Images are either served by this site or from an http(s) host the CSP can allow
+fn is_valid_image_url(url: &str) -> bool {
+    if url.chars().count() > MAX_IMAGE_URL_LENGTH || url.chars().any(char::is_whitespace) {
+        return false;
+    }
+    if url.starts_with('/') {
+        return !url.starts_with("//");
+    }
+    security::url_origin(url).is_some_and(|origin| security::is_valid_source(&origin))
+}
+
+fn validate_player_settings(
+    settings: PlayerSettings,
+    worlds: &WorldRegistry,
+) -> Result<PlayerSettings, String> {
+    let mut settings = PlayerSettings {
+        name: clean_setting(settings.name),
+        server: clean_setting(settings.server),
+        datacenter: clean_setting(settings.datacenter),
+        banner_image: clean_setting(settings.banner_image),
+        profile_image: clean_setting(settings.profile_image),
+        tagline: clean_setting(settings.tagline),
+    };
+
+    if let Some(name) = &settings.name {
+        if name.chars().count() > MAX_PLAYER_NAME_LENGTH {
+            return Err(format!(
+                "Name must be at most {MAX_PLAYER_NAME_LENGTH} characters"
+            ));
+        }
+    }
+    if let Some(tagline) = &settings.tagline {
+        if tagline.chars().count() > MAX_TAGLINE_LENGTH {
+            return Err(format!(
+                "Tagline must be at most {MAX_TAGLINE_LENGTH} characters"
+            ));
+        }
+    }
+    for (label, url) in [
+        ("Banner image", &settings.banner_image),
+        ("Profile image", &settings.profile_image),
+    ] {
+        if url.as_deref().is_some_and(|url| !is_valid_image_url(url)) {
+            return Err(format!(
+                "{label} must be a path on this site or an http(s) URL"
+            ));
+        }
+    }
+
+    // The world and data center are only meaningful together
+    match (&settings.server, &settings.datacenter) {
+        (None, None) => {}
+        (Some(server), Some(datacenter)) => {
+            let Some((dc, world)) = worlds.find_world(server) else {
+                return Err(format!("'{server}' is not a known FFXIV world"));
+            };
+            if !dc.name.eq_ignore_ascii_case(datacenter) {
+                return Err(format!("{server} is not on the {datacenter} data center"));
+            }
+            // Store the canonical spelling from the world list
+            settings.server = Some(world.name.clone());
+            settings.datacenter = Some(dc.name.clone());
+        }
+        _ => return Err("Choose both a world and a data center, or neither".to_string()),
+    }
+
+    Ok(settings)
+}
+
+pub async fn update_settings(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    body: web::Json<PlayerSettings>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Owner).await?;
+
+    let overrides = validate_player_settings(body.into_inner(), &data.settings.load().worlds)
+        .map_err(AppError::Validation)?;
+
+    let before = data.settings.load().player_overrides.clone();
+    save_audited_settings(&data, &admin, &before, &overrides).await?;
+
+    remove_replaced_upload(
+        &data,
+        before.banner_image.as_deref(),
+        overrides.banner_image.as_deref(),
+    );
+    remove_replaced_upload(
+        &data,
+        before.profile_image.as_deref(),
+        overrides.profile_image.as_deref(),
+    );
+    data.settings
+        .update(|current| current.with_player_overrides(overrides));
+    log::info!("Admin '{}' updated the player settings", admin.username);
+
+    Ok(HttpResponse::Ok().body("Saved"))
+}
+
+/// Store new player setting overrides and audit the change
+async fn save_audited_settings(
+    data: &AppState,
+    admin: &AdminIdentity,
+    before: &PlayerSettings,
+    overrides: &PlayerSettings,
+) -> Result<(), DbError> {
+    let saved = overrides.clone();
+    data.db
+        .write(move |conn| Ok(save_player_settings(conn, &saved)?))
+        .await?;
+    audit_event(
+        data,
+        admin,
+        AuditAction::SettingsUpdate,
+        Some("player"),
+        Some(before),
+        Some(overrides),
+    )
+    .await;
+    Ok(())
+}
+
+/// The file name of an image in the uploads directory, if a setting points at one
+fn uploaded_file_name(url: Option<&str>) -> Option<&str> {
+    url?.strip_prefix("/uploads/")
+        .filter(|name| !name.is_empty() && !name.contains(['/', '\\']) && !name.starts_with('.'))
+}
+
+/// Delete an uploaded image once a setting no longer points at it
+fn remove_replaced_upload(data: &AppState, before: Option<&str>, after: Option<&str>) {
+    if before == after {
+        return;
+    }
+    if let Some(old) = uploaded_file_name(before) {
+        if let Err(e) = std::fs::remove_file(data.uploads_dir.join(old)) {
+            log::warn!("Failed to remove old upload {}: {}", old, e);
+        }
+    }
+}
+
+pub async fn upload_image(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Owner).await?;
+
+    let Some(kind) = ImageKind::parse(&path.into_inner()) else {
+        return Err(AppError::NotFound("Unknown image".to_string()));
+    };
+
+    // Decoding and resizing is CPU heavy, so keep it off the request workers
+    let uploads_dir = data.uploads_dir.clone();
+    let stored = web::block(move || {
+        let webp = uploads::process_image(&body, kind)?;
+        uploads::store_image(&uploads_dir, kind, &webp).map_err(|e| {
+            log::error!("Failed to store uploaded image: {}", e);
+            "Failed to store image".to_string()
+        })
+    })
+    .await;
+    let file_name = stored
+        .map_err(|e| AppError::Internal(format!("Image processing failed: {e}")))?
+        .map_err(AppError::Validation)?;
+    let url = format!("/uploads/{file_name}");
+
+    let before = data.settings.load().player_overrides.clone();
+    let mut overrides = before.clone();
+    let slot = match kind {
+        ImageKind::Banner => &mut overrides.banner_image,
+        ImageKind::Profile => &mut overrides.profile_image,
+    };
+    let previous = slot.replace(url.clone());
+
+    if let Err(e) = save_audited_settings(&data, &admin, &before, &overrides).await {
+        let _ = std::fs::remove_file(data.uploads_dir.join(&file_name));
+        return Err(e.into());
+    }
+
+    data.settings
+        .update(|current| current.with_player_overrides(overrides));
+    log::info!(
+        "Admin '{}' uploaded a new {} image",
+        admin.username,
+        kind.as_str()
+    );
+
+    remove_replaced_upload(&data, previous.as_deref(), Some(&url));
+
+    Ok(HttpResponse::Ok().json(json!({ "url": url })))
+}
+
+// Backups
+
+pub async fn create_backup(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+    let admin = check_admin_auth(&req, &data, Role::Owner).await?;
+
+    let pool = data.db.clone();
+    let dir = data.backup_dir.clone();
+    let keep = data.backup_keep;
+    // Copying from a reader leaves the writer free while the backup runs
+    let path = web::block(move || backup::run_backup(&pool.reader(), &dir, keep))
+        .await
+        .map_err(|e| AppError::Internal(format!("Backup task failed: {e}")))?
+        .map_err(|e| AppError::Internal(format!("Backup failed: {e}")))?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    audit_event::<()>(
+        &data,
+        &admin,
+        AuditAction::Backup,
+        Some(&file_name),
+        None,
+        None,
+    )
+    .await;
+    log::info!(
+        "Admin '{}' backed up the database to {}",
+        admin.username,
+        path.display()
+    );
+
+    Ok(HttpResponse::Ok().json(json!({ "file": file_name })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryRepository;
+
+    fn test_db() -> Connection {
+        db::init_database(":memory:").expect("in-memory database")
+    }
+
+    fn feedback(id: &str, ip_address: &str) -> Feedback {
+        Feedback {
+            id: id.to_string(),
+            character_name: None,
+            server: None,
+            is_anonymous: true,
+            rating_mechanics: 4,
+            rating_damage: 4,
+            rating_teamwork: 4,
+            rating_communication: 4,
+            rating_overall: 4,
+            comments: None,
+            content_type: None,
+            player_job: None,
+            duty: None,
+            ip_address: ip_address.to_string(),
+            created_at: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            deleted_at: None,
+            is_published: false,
+            tags: Vec::new(),
+        }
+    }
+
+    fn moderator() -> AdminIdentity {
+        AdminIdentity {
+            username: "mod".to_string(),
+            role: Role::Moderator,
+            ip_address: "192.0.2.1".to_string(),
+            csrf_token: String::new(),
+        }
+    }
+
+    fn audit_actions(repo: &MemoryRepository) -> Vec<String> {
+        repo.audit_log()
+            .into_iter()
+            .map(|entry| entry.action)
+            .collect()
+    }
+
+    #[test]
+    fn submissions_hit_the_device_then_the_ip_limit() {
+        let repo = MemoryRepository::new();
+        assert!(matches!(
+            check_submission(&repo, "198.51.100.1", "device-a", 2).unwrap(),
+            SubmissionCheck::Allowed
+        ));
+
+        store_submission(&repo, &feedback("one", "198.51.100.1"), "device-a").unwrap();
+        assert!(matches!(
+            check_submission(&repo, "198.51.100.1", "device-a", 2).unwrap(),
+            SubmissionCheck::Limited(RateLimitType::CookieSoftLimit)
+        ));
+        assert!(matches!(
+            check_submission(&repo, "198.51.100.1", "device-b", 2).unwrap(),
+            SubmissionCheck::Allowed
+        ));
+
+        // Rejected attempts count towards the IP's hard limit too
+        repo.record_ip_attempt("198.51.100.1").unwrap();
+        assert!(matches!(
+            check_submission(&repo, "198.51.100.1", "device-b", 2).unwrap(),
+            SubmissionCheck::Limited(RateLimitType::IpHardLimit)
+        ));
+        assert!(matches!(
+            check_submission(&repo, "198.51.100.2", "device-c", 2).unwrap(),
+            SubmissionCheck::Allowed
+        ));
+    }
+
+    #[test]
+    fn banning_the_submitter_blocks_their_ip() {
+        let repo = MemoryRepository::new();
+        store_submission(&repo, &feedback("one", "198.51.100.1"), "device-a").unwrap();
+
+        let outcome =
+            apply_feedback_action(&repo, &moderator(), BulkAction::Ban, "one", None).unwrap();
+        assert_eq!(outcome, Ok(None));
+        assert!(matches!(
+            check_submission(&repo, "198.51.100.1", "device-b", 100).unwrap(),
+            SubmissionCheck::Banned
+        ));
+        assert_eq!(repo.stats().unwrap().banned_ips, 1);
+        assert_eq!(audit_actions(&repo), ["ban"]);
+    }
+
+    #[test]
+    fn trash_round_trip_is_audited() {
+        let repo = MemoryRepository::new();
+        let admin = moderator();
+        store_submission(&repo, &feedback("one", "198.51.100.1"), "device-a").unwrap();
+
+        // Purging only works on trashed entries
+        assert!(
+            !record_trash_change(&repo, &admin, AuditAction::Purge, "one", |repo, id| {
+                repo.purge(id)
+            })
+            .unwrap()
+        );
+
+        let outcome =
+            apply_feedback_action(&repo, &admin, BulkAction::Delete, "one", None).unwrap();
+        assert_eq!(outcome, Ok(None));
+        assert!(repo.list(&FeedbackFilter::live()).unwrap().is_empty());
+        assert_eq!(repo.list(&FeedbackFilter::trash()).unwrap().len(), 1);
+
+        assert!(
+            record_trash_change(&repo, &admin, AuditAction::Restore, "one", |repo, id| {
+                repo.restore(id)
+            })
+            .unwrap()
+        );
+        assert_eq!(repo.list(&FeedbackFilter::live()).unwrap().len(), 1);
+
+        apply_feedback_action(&repo, &admin, BulkAction::Delete, "one", None)
+            .unwrap()
+            .unwrap();
+        assert!(
+            record_trash_change(&repo, &admin, AuditAction::Purge, "one", |repo, id| {
+                repo.purge(id)
+            })
+            .unwrap()
+        );
+        assert_eq!(repo.get("one").unwrap(), None);
+
+        assert_eq!(
+            audit_actions(&repo),
+            ["delete", "restore", "delete", "purge"]
+        );
+        let log = repo.audit_log();
+        let purge = log.last().unwrap();
+        assert_eq!(purge.admin, "mod");
+        assert_eq!(purge.ip_address, "192.0.2.1");
+        assert_eq!(purge.target.as_deref(), Some("one"));
+        assert!(purge.before.is_some());
+        assert_eq!(purge.after, None);
+    }
+
+    #[test]
+    fn bulk_actions_report_per_id_failures() {
+        let repo = MemoryRepository::new();
+        let admin = moderator();
+        store_submission(&repo, &feedback("one", "198.51.100.1"), "device-a").unwrap();
+
+        assert_eq!(
+            apply_feedback_action(&repo, &admin, BulkAction::Publish, "missing", None).unwrap(),
+            Err("Feedback not found".to_string())
+        );
+        apply_feedback_action(&repo, &admin, BulkAction::Tag, "one", Some("raid"))
+            .unwrap()
+            .unwrap();
+        apply_feedback_action(&repo, &admin, BulkAction::Publish, "one", None)
+            .unwrap()
+            .unwrap();
+        let stored = repo.get("one").unwrap().unwrap();
+        assert!(stored.is_published);
+        assert_eq!(stored.tags, ["raid"]);
+
+        let exported =
+            apply_feedback_action(&repo, &admin, BulkAction::Export, "one", None).unwrap();
+        assert_eq!(exported, Ok(Some(stored)));
+        assert_eq!(audit_actions(&repo), ["tag", "publish", "export"]);
+    }
+
+    #[test]
+    fn bulk_actions_skip_trashed_feedback_except_export() {
+        let repo = MemoryRepository::new();
+        let admin = moderator();
+        store_submission(&repo, &feedback("one", "198.51.100.1"), "device-a").unwrap();
+        apply_feedback_action(&repo, &admin, BulkAction::Delete, "one", None)
+            .unwrap()
+            .unwrap();
+
+        for action in [
+            BulkAction::Delete,
+            BulkAction::Publish,
+            BulkAction::Unpublish,
+            BulkAction::Tag,
+            BulkAction::Ban,
+        ] {
+            assert_eq!(
+                apply_feedback_action(&repo, &admin, action, "one", Some("raid")).unwrap(),
+                Err("Feedback is in the trash".to_string()),
+                "{action:?}"
+            );
+        }
+        let stored = repo.get("one").unwrap().unwrap();
+        assert!(!stored.is_published);
+        assert!(stored.tags.is_empty());
+        assert_eq!(repo.stats().unwrap().banned_ips, 0);
+
+        let exported =
+            apply_feedback_action(&repo, &admin, BulkAction::Export, "one", None).unwrap();
+        assert_eq!(exported, Ok(Some(stored)));
+        assert_eq!(audit_actions(&repo), ["delete", "export"]);
+    }
+
+    #[test]
+    fn totp_step_is_only_accepted_once() {
+        let conn = test_db();
+        let id = db::create_admin(&conn, "alice", "hash", Role::Owner).unwrap();
+        let secret = totp::generate_secret();
+        db::set_pending_totp_secret(&conn, id, &secret).unwrap();
+        db::enable_totp(&conn, id, 0, &[]).unwrap();
+
+        let now = chrono::Utc::now().timestamp() as u64;
+        let raw = totp::base32_decode(&secret).unwrap();
+        let code = format!(
+            "{:06}",
+            totp::hotp(&raw, now / totp::STEP_SECONDS, totp::DIGITS)
+        );
+
+        assert!(verify_second_factor(&conn, id, &code).unwrap());
+        assert!(!verify_second_factor(&conn, id, &code).unwrap());
     }
 }
@@ -0,0 +1,231 @@
+use crate::models::RatingTally;
+
+/// The highest level a job can reach unless it says otherwise
+const LEVEL_CAP: u8 = 100;
+
+/// A combat role, used to group jobs in the pickers and in stats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobRole {
+    Tank,
+    Healer,
+    Melee,
+    Ranged,
+    Caster,
+}
+
+impl JobRole {
+    pub const ALL: &'static [JobRole] = &[
+        JobRole::Tank,
+        JobRole::Healer,
+        JobRole::Melee,
+        JobRole::Ranged,
+        JobRole::Caster,
+    ];
+
+    /// Heading for the role's jobs in the pickers
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobRole::Tank => "Tanks",
+            JobRole::Healer => "Healers",
+            JobRole::Melee => "Melee DPS",
+            JobRole::Ranged => "Physical Ranged DPS",
+            JobRole::Caster => "Magical Ranged DPS",
+        }
+    }
+
+    /// The role's regular jobs in picker order. Limited jobs are listed separately.
+    pub fn jobs(&self) -> impl Iterator<Item = Job> + '_ {
+        Job::ALL
+            .iter()
+            .copied()
+            .filter(move |job| job.role() == *self && !job.is_limited())
+    }
+}
+
+/// A job the player can be rated on, stored by its three-letter code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    Paladin,
+    Warrior,
+    DarkKnight,
+    Gunbreaker,
+    WhiteMage,
+    Scholar,
+    Astrologian,
+    Sage,
+    Monk,
+    Dragoon,
+    Ninja,
+    Samurai,
+    Reaper,
+    Viper,
+    Bard,
+    Machinist,
+    Dancer,
+    BlackMage,
+    Summoner,
+    RedMage,
+    Pictomancer,
+    BlueMage,
+}
+
+impl Job {
+    pub const ALL: &'static [Job] = &[
+        Job::Paladin,
+        Job::Warrior,
+        Job::DarkKnight,
+        Job::Gunbreaker,
+        Job::WhiteMage,
+        Job::Scholar,
+        Job::Astrologian,
+        Job::Sage,
+        Job::Monk,
+        Job::Dragoon,
+        Job::Ninja,
+        Job::Samurai,
+        Job::Reaper,
+        Job::Viper,
+        Job::Bard,
+        Job::Machinist,
+        Job::Dancer,
+        Job::BlackMage,
+        Job::Summoner,
+        Job::RedMage,
+        Job::Pictomancer,
+        Job::BlueMage,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Job::Paladin => "PLD",
+            Job::Warrior => "WAR",
+            Job::DarkKnight => "DRK",
+            Job::Gunbreaker => "GNB",
+            Job::WhiteMage => "WHM",
+            Job::Scholar => "SCH",
+            Job::Astrologian => "AST",
+            Job::Sage => "SGE",
+            Job::Monk => "MNK",
+            Job::Dragoon => "DRG",
+            Job::Ninja => "NIN",
+            Job::Samurai => "SAM",
+            Job::Reaper => "RPR",
+            Job::Viper => "VPR",
+            Job::Bard => "BRD",
+            Job::Machinist => "MCH",
+            Job::Dancer => "DNC",
+            Job::BlackMage => "BLM",
+            Job::Summoner => "SMN",
+            Job::RedMage => "RDM",
+            Job::Pictomancer => "PCT",
+            Job::BlueMage => "BLU",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::Paladin => "Paladin",
+            Job::Warrior => "Warrior",
+            Job::DarkKnight => "Dark Knight",
+            Job::Gunbreaker => "Gunbreaker",
+            Job::WhiteMage => "White Mage",
+            Job::Scholar => "Scholar",
+            Job::Astrologian => "Astrologian",
+            Job::Sage => "Sage",
+            Job::Monk => "Monk",
+            Job::Dragoon => "Dragoon",
+            Job::Ninja => "Ninja",
+            Job::Samurai => "Samurai",
+            Job::Reaper => "Reaper",
+            Job::Viper => "Viper",
+            Job::Bard => "Bard",
+            Job::Machinist => "Machinist",
+            Job::Dancer => "Dancer",
+            Job::BlackMage => "Black Mage",
+            Job::Summoner => "Summoner",
+            Job::RedMage => "Red Mage",
+            Job::Pictomancer => "Pictomancer",
+            Job::BlueMage => "Blue Mage",
+        }
+    }
+
+    pub fn role(&self) -> JobRole {
+        match self {
+            Job::Paladin | Job::Warrior | Job::DarkKnight | Job::Gunbreaker => JobRole::Tank,
+            Job::WhiteMage | Job::Scholar | Job::Astrologian | Job::Sage => JobRole::Healer,
+            Job::Monk | Job::Dragoon | Job::Ninja | Job::Samurai | Job::Reaper | Job::Viper => {
+                JobRole::Melee
+            }
+            Job::Bard | Job::Machinist | Job::Dancer => JobRole::Ranged,
+            Job::BlackMage | Job::Summoner | Job::RedMage | Job::Pictomancer | Job::BlueMage => {
+                JobRole::Caster
+            }
+        }
+    }
+
+    /// Limited jobs can't join regular duty finder content and have a lower level cap
+    pub fn is_limited(&self) -> bool {
+        matches!(self, Job::BlueMage)
+    }
+
+    pub fn level_cap(&self) -> u8 {
+        match self {
+            Job::BlueMage => 80,
+            _ => LEVEL_CAP,
+        }
+    }
+
+    /// The job's icon, served from the built-in assets
+    pub fn icon(&self) -> String {
+        format!(
+            "/assets/jobIcons/{}.png",
+            self.name().replace(' ', "").to_lowercase()
+        )
+    }
+
+    /// Jobs are matched by code, e.g. `WAR`
+    pub fn parse(value: &str) -> Option<Job> {
+        Job::ALL
+            .iter()
+            .copied()
+            .find(|job| job.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+
+    /// Limited jobs in picker order, listed after the roles
+    pub fn limited() -> impl Iterator<Item = Job> {
+        Job::ALL.iter().copied().filter(Job::is_limited)
+    }
+}
+
+/// Live feedback for one role, see `by_role`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleStats {
+    pub role: JobRole,
+    pub count: i64,
+    pub average_overall: f64,
+}
+
+/// Add up per-job tallies by role, in role order. Roles without feedback are left
+/// out, as are job values that aren't a known job code.
+pub fn by_role<'a>(per_job: impl IntoIterator<Item = &'a RatingTally>) -> Vec<RoleStats> {
+    let mut totals = vec![RatingTally::default(); JobRole::ALL.len()];
+    for tally in per_job {
+        let Some(job) = Job::parse(&tally.key) else {
+            continue;
+        };
+        if let Some(i) = JobRole::ALL.iter().position(|role| *role == job.role()) {
+            totals[i].count += tally.count;
+            totals[i].overall_total += tally.overall_total;
+        }
+    }
+    JobRole::ALL
+        .iter()
+        .zip(totals)
+        .filter(|(_, total)| total.count > 0)
+        .map(|(role, total)| RoleStats {
+            role: *role,
+            count: total.count,
+            average_overall: total.average_overall(),
+        })
+        .collect()
+}
@@ -0,0 +1,149 @@
+//! FinalFeedback, a self-hosted FFXIV performance feedback survey.
+//!
+//! The `final-feedback` binary is a thin wrapper around this crate. To mount the
+//! survey in another Actix site, open an [`AppState`] once, share it with every worker
+//! and register the routes with [`configure`]:
+//!
+//! ```no_run
+//! use actix_web::{middleware, web, App, HttpServer};
+//! use final_feedback::config::Config;
+//! use final_feedback::{error, security, AppState};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let config = Config::load(None).expect("invalid configuration");
+//! let state = web::Data::new(AppState::open(&config).await.expect("database"));
+//! HttpServer::new(move || {
+//!     App::new()
+//!         .app_data(state.clone())
+//!         .wrap(middleware::from_fn(error::error_pages))
+//!         .wrap(middleware::from_fn(security::security_headers))
+//!         .configure(final_feedback::configure)
+//! })
+//! .bind(("127.0.0.1", 8080))?
+//! .run()
+//! .await
+//! # }
+//! ```
+//!
+//! Routes use absolute paths (`/`, `/submit`, `/admin/...`, `/assets`, `/static` and
+//! `/uploads`), so register them at the root of the app rather than inside a scope.
+//! Trash purging and scheduled backups are started separately, see
+//! [`store::spawn_trash_purge`] and [`backup::spawn_schedule`].
+
+mod assets;
+mod auth;
+pub mod backup;
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod duties;
+pub mod error;
+pub mod handlers;
+pub mod jobs;
+pub mod models;
+#[cfg(feature = "postgres")]
+pub mod pg;
+pub mod pool;
+pub mod proxy;
+pub mod reload;
+pub mod repository;
+pub mod security;
+pub mod store;
+mod templates;
+mod totp;
+mod uploads;
+pub mod validation;
+pub mod worlds;
+
+use actix_web::{guard, web};
+
+pub use handlers::AppState;
+
+/// Register the survey, admin panel and asset routes. The app must also have a
+/// `web::Data<AppState>` and, for the CSP nonces the pages rely on, the
+/// `security::security_headers` middleware. Without `error::error_pages`, browsers
+/// are shown errors as JSON.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::FormConfig::default().error_handler(error::extractor_error))
+        .app_data(web::JsonConfig::default().error_handler(error::extractor_error))
+        .app_data(web::PathConfig::default().error_handler(error::extractor_error))
+        .app_data(web::QueryConfig::default().error_handler(error::extractor_error));
+    cfg
+        // Public routes
+        .route("/", web::get().to(handlers::index))
+        .route("/submit", web::post().to(handlers::submit_feedback))
+        // Admin routes (not linked from main site)
+        .route("/admin", web::get().to(handlers::admin_login))
+        .route("/admin/login", web::post().to(handlers::admin_login_submit))
+        .route("/admin/logout", web::post().to(handlers::admin_logout))
+        .route("/admin/panel", web::get().to(handlers::admin_panel))
+        .route(
+            "/admin/delete/{id}",
+            web::delete().to(handlers::delete_feedback),
+        )
+        .route("/admin/bulk", web::post().to(handlers::bulk_action))
+        .route("/admin/trash", web::get().to(handlers::admin_trash))
+        .route("/admin/audit", web::get().to(handlers::admin_audit))
+        .route("/admin/account", web::get().to(handlers::admin_account))
+        .route(
+            "/admin/account/totp/setup",
+            web::post().to(handlers::setup_totp),
+        )
+        .route(
+            "/admin/account/totp/enable",
+            web::post().to(handlers::enable_totp_for_account),
+        )
+        .route(
+            "/admin/account/totp/disable",
+            web::post().to(handlers::disable_totp_for_account),
+        )
+        .route("/admin/settings", web::get().to(handlers::admin_settings))
+        .route("/admin/settings", web::post().to(handlers::update_settings))
+        .route("/admin/backups", web::post().to(handlers::create_backup))
+        .service(
+            web::resource("/admin/settings/images/{kind}")
+                .app_data(web::PayloadConfig::new(uploads::MAX_UPLOAD_BYTES))
+                .route(web::post().to(handlers::upload_image)),
+        )
+        .route("/admin/users", web::get().to(handlers::admin_users))
+        .route(
+            "/admin/users",
+            web::post().to(handlers::create_admin_account),
+        )
+        .route(
+            "/admin/users/{id}/role",
+            web::post().to(handlers::update_admin_role),
+        )
+        .route(
+            "/admin/users/{id}",
+            web::delete().to(handlers::delete_admin_account),
+        )
+        .route(
+            "/admin/restore/{id}",
+            web::post().to(handlers::restore_deleted_feedback),
+        )
+        .route(
+            "/admin/purge/{id}",
+            web::delete().to(handlers::purge_deleted_feedback),
+        )
+        // Static assets
+        // Built into the binary, with files in ASSETS_DIR taking precedence
+        .route(
+            "/assets/{path:.*}",
+            web::route()
+                .guard(guard::Any(guard::Get()).or(guard::Head()))
+                .to(assets::assets),
+        )
+        .route(
+            "/static/{path:.*}",
+            web::route()
+                .guard(guard::Any(guard::Get()).or(guard::Head()))
+                .to(assets::static_files),
+        )
+        .route(
+            "/uploads/{name}",
+            web::route()
+                .guard(guard::Any(guard::Get()).or(guard::Head()))
+                .to(uploads::serve),
+        );
+}
@@ -1,158 +1,158 @@
-mod db;
-mod handlers;
-mod models;
-mod templates;
-
-use actix_files as fs;
 use actix_web::{middleware, web, App, HttpServer};
-use parking_lot::Mutex;
 use std::env;
 use std::sync::Arc;
 
-use handlers::AppState;
-use templates::PlayerConfig;
+use final_feedback::config::{self, Config, EnvFile};
+use final_feedback::reload::{self, ReloadSources};
+use final_feedback::{backup, cli, error, security, store, AppState};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     // Initialize logging
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
 
-    // Load environment variables from .env file if present
-    // Use manifest directory to find .env from the crate root
-    let manifest_dir = env!("CARGO_MANIFEST_DIR");
-    let env_file = std::path::PathBuf::from(manifest_dir).join(".env");
-    if env_file.exists() {
-        log::info!("Loading .env from: {}", env_file.display());
-        match dotenvy::from_path(&env_file) {
-            Ok(_) => log::info!(".env loaded successfully"),
-            Err(e) => log::error!("Failed to load .env: {e:?}"),
+    // Read a .env file from the working directory or a parent, if present. Its
+    // variables are handed to the config rather than set on the process, and the
+    // real environment wins over them.
+    let env_path = config::find_env_file();
+    let env_file = match &env_path {
+        Some(path) => match config::read_env_file(path) {
+            Ok(env_file) => {
+                log::info!("Loaded .env from: {}", path.display());
+                env_file
+            }
+            Err(e) => {
+                log::error!("Failed to load .env: {e}");
+                EnvFile::new()
+            }
+        },
+        None => EnvFile::new(),
+    };
+
+    let (config_path, args) = match cli::split_config_flag(env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("{message}\n\n{}", cli::USAGE);
+            std::process::exit(2);
         }
-    } else {
-        log::warn!(".env file not found at: {}", env_file.display());
-        dotenvy::dotenv().ok();
+    };
+    let config = Config::load_with_env_file(config_path.as_deref(), &env_file);
+
+    if args.first().map(String::as_str) == Some("check-config") {
+        std::process::exit(cli::check_config(config_path.as_deref(), &config));
     }
 
-    // Configuration
-    let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
-    let db_path = env::var("DATABASE_PATH").unwrap_or_else(|_| "feedback.db".to_string());
-    let (admin_password, is_default_admin_password) = match env::var("ADMIN_PASSWORD") {
-        Ok(pass) => (pass, false),
-        Err(_) => {
-            log::warn!(
-                "ADMIN_PASSWORD not set, using default 'admin123' - CHANGE THIS IN PRODUCTION!"
-            );
-            ("admin123".to_string(), true)
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            log::error!("{e}");
+            std::process::exit(1);
         }
     };
-    let discord_webhook_url = env::var("DISCORD_WEBHOOK_URL").ok();
-
-    // Player configuration
-    let player_name = env::var("PLAYER_NAME").unwrap_or_else(|_| "Your Character".to_string());
-    let player_server = env::var("PLAYER_SERVER").unwrap_or_else(|_| "Server".to_string());
-    let player_datacenter =
-        env::var("PLAYER_DATACENTER").unwrap_or_else(|_| "Datacenter".to_string());
-    let banner_image =
-        env::var("BANNER_IMAGE").unwrap_or_else(|_| "/assets/banner.webp".to_string());
-    let profile_image =
-        env::var("PROFILE_IMAGE").unwrap_or_else(|_| "/assets/profile.webp".to_string());
-    let tagline = env::var("TAGLINE")
-        .unwrap_or_else(|_| "Ran content with me? Let me know how I did!".to_string());
-    let rate_limit_minutes = env::var("RATE_LIMIT_MINUTES")
-        .ok()
-        .and_then(|v| v.parse::<i64>().ok())
-        .unwrap_or(30);
-    let ip_rate_limit_max = env::var("IP_RATE_LIMIT_MAX")
-        .ok()
-        .and_then(|v| v.parse::<i64>().ok())
-        .unwrap_or(10);
-
-    // Parse trusted proxy IPs (comma-separated)
-    // Example: "127.0.0.1,192.168.1.1"
-    let trusted_proxy_ips: Vec<String> = env::var("TRUSTED_PROXY_IPS")
-        .unwrap_or_default()
-        .split(',')
-        .map(|ip| ip.trim().to_string())
-        .filter(|ip| !ip.is_empty())
-        .collect();
-
-    // Parse filter words (comma-separated)
-    // Example: "SEO,spam,phishing"
-    let filter_words: Vec<String> = env::var("FILTER_WORDS")
-        .unwrap_or_default()
-        .split(',')
-        .map(|word| word.trim().to_string().to_lowercase())
-        .filter(|word| !word.is_empty())
-        .collect();
-
-    let player = PlayerConfig {
-        name: player_name,
-        server: player_server,
-        datacenter: player_datacenter,
-        banner_image,
-        profile_image,
-        tagline,
-    };
 
-    if discord_webhook_url.is_some() {
+    // Maintenance subcommands run instead of the server, off the async runtime since
+    // they block on the database
+    if let Some(command) = args.first().filter(|command| *command != "serve").cloned() {
+        let config = config.clone();
+        let code = tokio::task::spawn_blocking(move || cli::run(&command, &args[1..], &config))
+            .await
+            .unwrap_or(1);
+        std::process::exit(code);
+    }
+    if args.len() > 1 {
+        eprintln!("serve takes no arguments\n\n{}", cli::USAGE);
+        std::process::exit(2);
+    }
+
+    if let Some(path) = &config_path {
+        log::info!("Loaded configuration from {}", path.display());
+    }
+
+    if config.discord_webhook_url.is_some() {
         log::info!("Discord webhook notifications enabled");
     }
 
-    if is_default_admin_password {
-        log::error!("WARNING: Using default admin password! Admin panel will show error page until ADMIN_PASSWORD is set.");
+    if config.is_default_admin_password {
+        log::warn!("ADMIN_PASSWORD not set, using default 'admin123' - CHANGE THIS IN PRODUCTION!");
+        log::error!("WARNING: Using default admin password! Admin panel will show error page until ADMIN_PASSWORD is set or an admin account is created.");
     }
 
-    if !trusted_proxy_ips.is_empty() {
-        log::info!("Trusted proxy IPs: {}", trusted_proxy_ips.join(", "));
+    if !config.trusted_proxies.is_empty() {
+        log::info!("Trusted proxies: {}", config.trusted_proxies.describe());
     } else {
         log::warn!("No trusted proxies configured - X-Forwarded-For headers will be ignored");
     }
 
     log::info!(
         "Player: {} @ {} ({})",
-        player.name,
-        player.server,
-        player.datacenter
+        config.player.name,
+        config.player.server,
+        config.player.datacenter
     );
-    log::info!("Rate limit window: {rate_limit_minutes} minutes");
-    log::info!("Filter words: {}", filter_words.join(", "));
+    if !config.security.img_sources().is_empty() {
+        log::info!(
+            "Extra CSP image sources: {}",
+            config.security.img_sources().join(" ")
+        );
+    }
+    if let Some(max_age) = config.security.hsts_max_age() {
+        log::info!("HSTS enabled with max-age {max_age}");
+    }
+    log::info!("Rate limit window: {} minutes", config.rate_limit_minutes);
+    log::info!("Trash retention: {} days", config.trash_retention_days);
+    if config.backup_interval_hours > 0 {
+        log::info!(
+            "Backups every {} hours to {}, keeping {}",
+            config.backup_interval_hours,
+            config.backup_dir,
+            config.backup_keep
+        );
+    } else {
+        log::info!("Scheduled backups disabled");
+    }
+    log::info!("Filter words: {}", config.filter_words.join(", "));
 
     // Initialize database
-    let conn = db::init_database(&db_path).expect("Failed to initialize database");
-    let db_pool = Arc::new(Mutex::new(conn));
+    let state = AppState::open(&config)
+        .await
+        .expect("Failed to initialize database");
+
+    if let Err(e) = std::fs::create_dir_all(&config.uploads_dir) {
+        log::error!(
+            "Failed to create uploads directory {}: {e}",
+            config.uploads_dir
+        );
+    }
+
+    if config.backup_interval_hours > 0 {
+        backup::spawn_schedule(
+            state.db.clone(),
+            config.backup_dir.clone().into(),
+            config.backup_interval_hours,
+            config.backup_keep,
+        );
+    }
+    store::spawn_trash_purge(state.store.clone(), config.trash_retention_days);
+
+    // Player profile, filter words and notifier settings can change without a restart
+    reload::spawn_watchers(
+        Arc::new(ReloadSources::new(config_path, env_path, config.clone())),
+        state.settings.clone(),
+    );
 
-    let bind_addr = format!("{}:{}", host, port);
+    let bind_addr = format!("{}:{}", config.host, config.port);
     log::info!("Starting server at http://{}", bind_addr);
     log::info!("Admin panel available at http://{}/admin/panel", bind_addr);
 
+    let state = web::Data::new(state);
     HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(AppState {
-                db: db_pool.clone(),
-                admin_password: admin_password.clone(),
-                discord_webhook_url: discord_webhook_url.clone(),
-                player: player.clone(),
-                rate_limit_minutes,
-                ip_rate_limit_max,
-                trusted_proxy_ips: trusted_proxy_ips.clone(),
-                is_default_admin_password,
-                filter_words: filter_words.clone(),
-            }))
+            .app_data(state.clone())
+            .wrap(middleware::from_fn(error::error_pages))
+            .wrap(middleware::from_fn(security::security_headers))
             .wrap(middleware::Logger::default())
             .wrap(middleware::Compress::default())
-            // Public routes
-            .route("/", web::get().to(handlers::index))
-            .route("/submit", web::post().to(handlers::submit_feedback))
-            // Admin routes (not linked from main site)
-            .route("/admin", web::get().to(handlers::admin_login))
-            .route("/admin/panel", web::get().to(handlers::admin_panel))
-            .route(
-                "/admin/delete/{id}",
-                web::delete().to(handlers::delete_feedback),
-            )
-            // Static assets
-            .service(fs::Files::new("/assets", "src/assets").use_last_modified(true))
-            .service(fs::Files::new("/static", "static").use_last_modified(true))
+            .configure(final_feedback::configure)
     })
     .bind(&bind_addr)?
     .run()
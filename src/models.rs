@@ -11,23 +11,41 @@ where
         .unwrap_or(false))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn deserialize_rating<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // A missing or garbled rating is reported next to its field instead of failing
+    // the whole form
+    let opt: Option<String> = Option::deserialize(deserializer)?;
+    Ok(opt.and_then(|s| s.trim().parse().ok()))
+}
+
+/// The feedback form as posted, before validation. Also used to fill the form back
+/// in when a submission is rejected.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct FeedbackSubmission {
     pub character_name: Option<String>,
     pub server: Option<String>,
     #[serde(default, deserialize_with = "deserialize_checkbox")]
     pub is_anonymous: bool,
-    pub rating_mechanics: i32,
-    pub rating_damage: i32,
-    pub rating_teamwork: i32,
-    pub rating_communication: i32,
-    pub rating_overall: i32,
+    #[serde(default, deserialize_with = "deserialize_rating")]
+    pub rating_mechanics: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_rating")]
+    pub rating_damage: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_rating")]
+    pub rating_teamwork: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_rating")]
+    pub rating_communication: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_rating")]
+    pub rating_overall: Option<i32>,
     pub comments: Option<String>,
     pub content_type: Option<String>,
     pub player_job: Option<String>,
+    pub duty: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Feedback {
     pub id: String,
     pub character_name: Option<String>,
@@ -41,12 +59,17 @@ pub struct Feedback {
     pub comments: Option<String>,
     pub content_type: Option<String>,
     pub player_job: Option<String>,
+    /// The duty from the catalogue, missing from exports made before it existed
+    #[serde(default)]
+    pub duty: Option<String>,
     pub ip_address: String,
     pub created_at: String,
+    pub deleted_at: Option<String>,
+    pub is_published: bool,
+    pub tags: Vec<String>,
 }
 
 impl Feedback {
-    #[allow(dead_code)]
     pub fn average_rating(&self) -> f32 {
         (self.rating_mechanics
             + self.rating_damage
@@ -57,108 +80,362 @@ impl Feedback {
     }
 }
 
-// FFXIV Server list for validation
-pub const FFXIV_SERVERS: &[&str] = &[
-    // NA - Aether
-    "Adamantoise",
-    "Cactuar",
-    "Faerie",
-    "Gilgamesh",
-    "Jenova",
-    "Midgardsormr",
-    "Sargatanas",
-    "Siren",
-    // NA - Crystal
-    "Balmung",
-    "Brynhildr",
-    "Coeurl",
-    "Diabolos",
-    "Goblin",
-    "Malboro",
-    "Mateus",
-    "Zalera",
-    // NA - Primal
-    "Behemoth",
-    "Excalibur",
-    "Exodus",
-    "Famfrit",
-    "Hyperion",
-    "Lamia",
-    "Leviathan",
-    "Ultros",
-    // NA - Dynamis
-    "Halicarnassus",
-    "Maduin",
-    "Marilith",
-    "Seraph",
-    "Cuchulainn",
-    "Golem",
-    "Kraken",
-    "Rafflesia",
-    // EU - Chaos
-    "Cerberus",
-    "Louisoix",
-    "Moogle",
-    "Omega",
-    "Phantom",
-    "Ragnarok",
-    "Sagittarius",
-    "Spriggan",
-    // EU - Light
-    "Alpha",
-    "Lich",
-    "Odin",
-    "Phoenix",
-    "Raiden",
-    "Shiva",
-    "Twintania",
-    "Zodiark",
-    // JP - Elemental
-    "Aegis",
-    "Atomos",
-    "Carbuncle",
-    "Garuda",
-    "Gungnir",
-    "Kujata",
-    "Tonberry",
-    "Typhon",
-    // JP - Gaia
-    "Alexander",
-    "Bahamut",
-    "Durandal",
-    "Fenrir",
-    "Ifrit",
-    "Ridill",
-    "Tiamat",
-    "Ultima",
-    // JP - Mana
-    "Anima",
-    "Asura",
-    "Chocobo",
-    "Hades",
-    "Ixion",
-    "Masamune",
-    "Pandaemonium",
-    "Titan",
-    // JP - Meteor
-    "Belias",
-    "Mandragora",
-    "Ramuh",
-    "Shinryu",
-    "Unicorn",
-    "Valefor",
-    "Yojimbo",
-    "Zeromus",
-    // OCE - Materia
-    "Bismarck",
-    "Ravana",
-    "Sephirot",
-    "Sophia",
-    "Zurvan",
-];
-
-pub fn is_valid_server(server: &str) -> bool {
-    FFXIV_SERVERS
-        .iter()
-        .any(|&s| s.eq_ignore_ascii_case(server))
+/// Which feedback `FeedbackRepository::list` returns
+#[derive(Debug, Clone, Default)]
+pub struct FeedbackFilter {
+    /// The trash bin instead of live feedback
+    pub trashed: bool,
+    pub published: Option<bool>,
+    pub tag: Option<String>,
+}
+
+impl FeedbackFilter {
+    pub fn live() -> Self {
+        FeedbackFilter::default()
+    }
+
+    pub fn trash() -> Self {
+        FeedbackFilter {
+            trashed: true,
+            ..FeedbackFilter::default()
+        }
+    }
+}
+
+/// Totals reported by the `stats` command
+#[derive(Debug, Serialize)]
+pub struct FeedbackStats {
+    pub live: i64,
+    pub trashed: i64,
+    pub published: i64,
+    pub banned_ips: i64,
+    /// Mean overall rating of live feedback, None when there is none
+    pub average_overall: Option<f64>,
+    /// Live feedback per stated world, anonymous feedback excluded
+    pub by_server: Vec<(String, i64)>,
+    /// Live feedback per job code, where one was given
+    pub by_job: Vec<RatingTally>,
+    /// Live feedback per duty, where one was picked
+    pub by_duty: Vec<RatingTally>,
+}
+
+/// How many live entries share a value, and the total of their overall ratings
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RatingTally {
+    pub key: String,
+    pub count: i64,
+    pub overall_total: i64,
+}
+
+impl RatingTally {
+    pub fn average_overall(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.overall_total as f64 / self.count as f64
+    }
+
+    /// Tally `(value, overall rating)` pairs by value, in order of first appearance
+    pub fn tally<'a>(entries: impl IntoIterator<Item = (&'a str, i32)>) -> Vec<RatingTally> {
+        let mut tallies: Vec<RatingTally> = Vec::new();
+        for (key, overall) in entries {
+            match tallies.iter_mut().find(|tally| tally.key == key) {
+                Some(tally) => {
+                    tally.count += 1;
+                    tally.overall_total += i64::from(overall);
+                }
+                None => tallies.push(RatingTally {
+                    key: key.to_string(),
+                    count: 1,
+                    overall_total: i64::from(overall),
+                }),
+            }
+        }
+        tallies
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Delete,
+    Publish,
+    Unpublish,
+    Tag,
+    Ban,
+    Export,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkActionRequest {
+    pub action: BulkAction,
+    pub ids: Vec<String>,
+    /// Only used by the `tag` action
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkActionResult {
+    pub id: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Only populated by the `export` action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+}
+
+/// The kind of duty the feedback is about, stored by the name the form submits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Dungeon,
+    Trial,
+    NormalRaid,
+    AllianceRaid,
+    ExtremeTrial,
+    SavageRaid,
+    UltimateRaid,
+    DeepDungeon,
+    VariantDungeon,
+    Criterion,
+    Other,
+}
+
+impl ContentType {
+    pub const ALL: &'static [ContentType] = &[
+        ContentType::Dungeon,
+        ContentType::Trial,
+        ContentType::NormalRaid,
+        ContentType::AllianceRaid,
+        ContentType::ExtremeTrial,
+        ContentType::SavageRaid,
+        ContentType::UltimateRaid,
+        ContentType::DeepDungeon,
+        ContentType::VariantDungeon,
+        ContentType::Criterion,
+        ContentType::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentType::Dungeon => "Dungeon",
+            ContentType::Trial => "Trial",
+            ContentType::NormalRaid => "Normal Raid",
+            ContentType::AllianceRaid => "Alliance Raid",
+            ContentType::ExtremeTrial => "Extreme Trial",
+            ContentType::SavageRaid => "Savage Raid",
+            ContentType::UltimateRaid => "Ultimate Raid",
+            ContentType::DeepDungeon => "Deep Dungeon",
+            ContentType::VariantDungeon => "Variant Dungeon",
+            ContentType::Criterion => "Criterion",
+            ContentType::Other => "Other",
+        }
+    }
+
+    /// Content types are matched by the stored name, e.g. `Savage Raid`
+    pub fn parse(value: &str) -> Option<ContentType> {
+        ContentType::ALL
+            .iter()
+            .copied()
+            .find(|content| content.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+}
+
+/// Admin permission levels, each including everything below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only access to the panel and stats
+    Viewer,
+    /// Can also publish, tag, delete and restore feedback
+    Moderator,
+    /// Can also ban submitters, change configuration and manage admins
+    Owner,
+}
+
+impl Role {
+    pub const ALL: &'static [Role] = &[Role::Viewer, Role::Moderator, Role::Owner];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Moderator => "moderator",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Role> {
+        Role::ALL
+            .iter()
+            .copied()
+            .find(|role| role.as_str().eq_ignore_ascii_case(value.trim()))
+    }
+
+    pub fn allows(&self, required: Role) -> bool {
+        *self >= required
+    }
+}
+
+impl BulkAction {
+    pub fn required_role(&self) -> Role {
+        match self {
+            BulkAction::Export => Role::Viewer,
+            BulkAction::Delete | BulkAction::Publish | BulkAction::Unpublish | BulkAction::Tag => {
+                Role::Moderator
+            }
+            BulkAction::Ban => Role::Owner,
+        }
+    }
+}
+
+/// An admin account as shown in the panel (never includes the password hash)
+#[derive(Debug, Serialize)]
+pub struct AdminAccount {
+    pub id: i64,
+    pub username: String,
+    pub role: Role,
+    pub created_at: String,
+    pub totp_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminLoginForm {
+    pub username: String,
+    pub password: String,
+    /// Authenticator or recovery code, required once TOTP is enabled
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CsrfForm {
+    pub csrf_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewAdminRequest {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAdminRoleRequest {
+    pub role: Role,
+}
+
+/// Player profile values saved from the admin settings page. Unset fields fall back
+/// to the configured defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSettings {
+    pub name: Option<String>,
+    pub server: Option<String>,
+    pub datacenter: Option<String>,
+    pub banner_image: Option<String>,
+    pub profile_image: Option<String>,
+    pub tagline: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    LoginSuccess,
+    LoginFailure,
+    Delete,
+    Restore,
+    Purge,
+    Publish,
+    Unpublish,
+    Tag,
+    Ban,
+    Export,
+    AdminCreate,
+    AdminUpdate,
+    AdminDelete,
+    TotpEnable,
+    TotpDisable,
+    SettingsUpdate,
+    Backup,
+}
+
+impl AuditAction {
+    pub const ALL: &'static [AuditAction] = &[
+        AuditAction::LoginSuccess,
+        AuditAction::LoginFailure,
+        AuditAction::Delete,
+        AuditAction::Restore,
+        AuditAction::Purge,
+        AuditAction::Publish,
+        AuditAction::Unpublish,
+        AuditAction::Tag,
+        AuditAction::Ban,
+        AuditAction::Export,
+        AuditAction::AdminCreate,
+        AuditAction::AdminUpdate,
+        AuditAction::AdminDelete,
+        AuditAction::TotpEnable,
+        AuditAction::TotpDisable,
+        AuditAction::SettingsUpdate,
+        AuditAction::Backup,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LoginSuccess => "login_success",
+            AuditAction::LoginFailure => "login_failure",
+            AuditAction::Delete => "delete",
+            AuditAction::Restore => "restore",
+            AuditAction::Purge => "purge",
+            AuditAction::Publish => "publish",
+            AuditAction::Unpublish => "unpublish",
+            AuditAction::Tag => "tag",
+            AuditAction::Ban => "ban",
+            AuditAction::Export => "export",
+            AuditAction::AdminCreate => "admin_create",
+            AuditAction::AdminUpdate => "admin_update",
+            AuditAction::AdminDelete => "admin_delete",
+            AuditAction::TotpEnable => "totp_enable",
+            AuditAction::TotpDisable => "totp_disable",
+            AuditAction::SettingsUpdate => "settings_update",
+            AuditAction::Backup => "backup",
+        }
+    }
+}
+
+impl From<BulkAction> for AuditAction {
+    fn from(action: BulkAction) -> Self {
+        match action {
+            BulkAction::Delete => AuditAction::Delete,
+            BulkAction::Publish => AuditAction::Publish,
+            BulkAction::Unpublish => AuditAction::Unpublish,
+            BulkAction::Tag => AuditAction::Tag,
+            BulkAction::Ban => AuditAction::Ban,
+            BulkAction::Export => AuditAction::Export,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub created_at: String,
+    pub admin: String,
+    pub ip_address: String,
+    pub action: String,
+    pub target: Option<String>,
+    /// JSON snapshot of the affected record before the change
+    pub before: Option<String>,
+    /// JSON snapshot of the affected record after the change
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub action: Option<String>,
+    pub admin: Option<String>,
+    /// Matches against the target id or source IP
+    pub search: Option<String>,
 }
@@ -0,0 +1,750 @@
+use parking_lot::{Mutex, MutexGuard};
+use postgres::{Client, GenericClient, NoTls, Row};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::db::{RateLimitType, COOKIE_LIMIT_WINDOW_MINUTES, IP_LIMIT_WINDOW_MINUTES};
+use crate::models::{
+    AuditAction, AuditEntry, AuditFilter, Feedback, FeedbackFilter, FeedbackStats, RatingTally,
+};
+use crate::repository::{FeedbackRepository, RepoResult};
+
+/// Connections each instance keeps open to the server
+const CLIENT_CONNECTIONS: usize = 4;
+
+// Held while migrating, so instances starting together don't race to create tables
+const MIGRATION_LOCK: i64 = 0x4646_4d49_4752_4154;
+
+/// Schema changes, applied in order. The number applied so far is kept in
+/// `schema_version`, so append new steps rather than editing old ones.
+const MIGRATIONS: &[&str] = &[
+    // The same tables as the SQLite feedback schema, minus the admin accounts, sessions
+    // and settings that stay in each instance's SQLite database
+    "CREATE TABLE feedback (
+        id TEXT PRIMARY KEY,
+        character_name TEXT,
+        server TEXT,
+        is_anonymous BOOLEAN NOT NULL DEFAULT FALSE,
+        rating_mechanics INTEGER NOT NULL CHECK (rating_mechanics BETWEEN 1 AND 5),
+        rating_damage INTEGER NOT NULL CHECK (rating_damage BETWEEN 1 AND 5),
+        rating_teamwork INTEGER NOT NULL CHECK (rating_teamwork BETWEEN 1 AND 5),
+        rating_communication INTEGER NOT NULL CHECK (rating_communication BETWEEN 1 AND 5),
+        rating_overall INTEGER NOT NULL CHECK (rating_overall BETWEEN 1 AND 5),
+        comments TEXT,
+        content_type TEXT,
+        player_job TEXT,
+        ip_address TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        deleted_at TEXT,
+        is_published BOOLEAN NOT NULL DEFAULT FALSE
+    );
+    CREATE INDEX idx_feedback_created_at ON feedback (created_at);
+    CREATE INDEX idx_feedback_ip_address ON feedback (ip_address);
+    CREATE INDEX idx_feedback_deleted_at ON feedback (deleted_at);
+
+    CREATE TABLE feedback_tags (
+        feedback_id TEXT NOT NULL REFERENCES feedback (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (feedback_id, tag)
+    );
+
+    CREATE TABLE banned_ips (
+        ip_address TEXT PRIMARY KEY,
+        banned_at TEXT NOT NULL
+    );
+
+    CREATE TABLE cookie_submissions (
+        cookie_id TEXT PRIMARY KEY,
+        submitted_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_cookie_submitted_at ON cookie_submissions (submitted_at);
+
+    CREATE TABLE ip_attempts (
+        id BIGSERIAL PRIMARY KEY,
+        ip_address TEXT NOT NULL,
+        attempted_at TEXT NOT NULL
+    );
+    CREATE INDEX idx_ip_attempts_ip_address ON ip_attempts (ip_address);
+    CREATE INDEX idx_ip_attempts_attempted_at ON ip_attempts (attempted_at);
+
+    CREATE TABLE audit_log (
+        id BIGSERIAL PRIMARY KEY,
+        created_at TEXT NOT NULL,
+        admin TEXT NOT NULL,
+        ip_address TEXT NOT NULL,
+        action TEXT NOT NULL,
+        target TEXT,
+        before TEXT,
+        after TEXT
+    );
+    CREATE INDEX idx_audit_log_created_at ON audit_log (created_at);",
+    "ALTER TABLE feedback ADD COLUMN duty TEXT;",
+];
+
+fn now() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn minutes_ago(minutes: i64) -> String {
+    (chrono::Utc::now() - chrono::Duration::minutes(minutes))
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Bring the schema up to date and clear out expired rate-limit rows, like
+/// `db::init_database` does for SQLite
+fn migrate(client: &mut Client) -> Result<(), postgres::Error> {
+    let mut tx = client.transaction()?;
+    tx.execute("SELECT pg_advisory_xact_lock($1)", &[&MIGRATION_LOCK])?;
+    tx.batch_execute("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)")?;
+
+    let version: i32 = tx
+        .query_opt("SELECT version FROM schema_version", &[])?
+        .map(|row| row.get(0))
+        .unwrap_or(0);
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        tx.batch_execute(migration)?;
+    }
+    if version as usize != MIGRATIONS.len() {
+        tx.execute("DELETE FROM schema_version", &[])?;
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES ($1)",
+            &[&(MIGRATIONS.len() as i32)],
+        )?;
+        log::info!(
+            "Migrated PostgreSQL schema from version {version} to {}",
+            MIGRATIONS.len()
+        );
+    }
+
+    let cutoff = minutes_ago(60);
+    tx.execute(
+        "DELETE FROM cookie_submissions WHERE submitted_at < $1",
+        &[&cutoff],
+    )?;
+    tx.execute(
+        "DELETE FROM ip_attempts WHERE attempted_at < $1",
+        &[&cutoff],
+    )?;
+    tx.commit()
+}
+
+struct Clients {
+    url: String,
+    clients: Vec<Mutex<Client>>,
+    next_client: AtomicUsize,
+}
+
+impl Drop for Clients {
+    fn drop(&mut self) {
+        // Each client shuts down a runtime of its own when dropped, which tokio refuses
+        // to do on an async worker thread
+        let clients = std::mem::take(&mut self.clients);
+        let _ = std::thread::spawn(move || drop(clients)).join();
+    }
+}
+
+/// Connections to a PostgreSQL server shared by several instances. The client is
+/// synchronous, so like `DbPool` it must only be used from blocking threads.
+#[derive(Clone)]
+pub struct PgPool(Arc<Clients>);
+
+impl PgPool {
+    /// Connect and apply migrations through the first connection
+    pub fn connect(url: &str) -> Result<PgPool, postgres::Error> {
+        let mut first = Client::connect(url, NoTls)?;
+        migrate(&mut first)?;
+
+        let mut clients = vec![Mutex::new(first)];
+        for _ in 1..CLIENT_CONNECTIONS {
+            clients.push(Mutex::new(Client::connect(url, NoTls)?));
+        }
+
+        Ok(PgPool(Arc::new(Clients {
+            url: url.to_string(),
+            clients,
+            next_client: AtomicUsize::new(0),
+        })))
+    }
+
+    /// Lock a connection, preferring an idle one, and reconnect it if the server
+    /// closed it since it was last used
+    pub fn client(&self) -> Result<MutexGuard<'_, Client>, postgres::Error> {
+        let clients = &self.0.clients;
+        let start = self.0.next_client.fetch_add(1, Ordering::Relaxed);
+        let mut client = (0..clients.len())
+            .find_map(|i| clients[(start + i) % clients.len()].try_lock())
+            .unwrap_or_else(|| clients[start % clients.len()].lock());
+        if client.is_closed() {
+            log::warn!("PostgreSQL connection was closed, reconnecting");
+            *client = Client::connect(&self.0.url, NoTls)?;
+        }
+        Ok(client)
+    }
+}
+
+const FEEDBACK_COLUMNS: &str = "id, character_name, server, is_anonymous, rating_mechanics,
+    rating_damage, rating_teamwork, rating_communication, rating_overall, comments,
+    content_type, player_job, ip_address, created_at, deleted_at, is_published,
+    (SELECT string_agg(tag, ',' ORDER BY tag) FROM feedback_tags WHERE feedback_id = feedback.id),
+    duty";
+
+fn feedback_from_row(row: &Row) -> Feedback {
+    Feedback {
+        id: row.get(0),
+        character_name: row.get(1),
+        server: row.get(2),
+        is_anonymous: row.get(3),
+        rating_mechanics: row.get(4),
+        rating_damage: row.get(5),
+        rating_teamwork: row.get(6),
+        rating_communication: row.get(7),
+        rating_overall: row.get(8),
+        comments: row.get(9),
+        content_type: row.get(10),
+        player_job: row.get(11),
+        duty: row.get(17),
+        ip_address: row.get(12),
+        created_at: row.get(13),
+        deleted_at: row.get(14),
+        is_published: row.get(15),
+        tags: row
+            .get::<_, Option<String>>(16)
+            .map(|tags| tags.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// The PostgreSQL backend, over either a pooled connection or a transaction on one.
+/// The client needs `&mut` for every query, hence the `RefCell`.
+pub struct PgRepository<'a, C: GenericClient>(RefCell<&'a mut C>);
+
+impl<'a, C: GenericClient> PgRepository<'a, C> {
+    pub fn new(client: &'a mut C) -> Self {
+        PgRepository(RefCell::new(client))
+    }
+
+    fn execute(
+        &self,
+        sql: &str,
+        params: &[&(dyn postgres::types::ToSql + Sync)],
+    ) -> RepoResult<u64> {
+        Ok(self.0.borrow_mut().execute(sql, params)?)
+    }
+
+    fn count(&self, sql: &str, params: &[&(dyn postgres::types::ToSql + Sync)]) -> RepoResult<i64> {
+        Ok(self.0.borrow_mut().query_one(sql, params)?.get(0))
+    }
+}
+
+impl<C: GenericClient> FeedbackRepository for PgRepository<'_, C> {
+    fn insert(&self, feedback: &Feedback) -> RepoResult<bool> {
+        let rows = self.execute(
+            "INSERT INTO feedback (id, character_name, server, is_anonymous,
+             rating_mechanics, rating_damage, rating_teamwork, rating_communication,
+             rating_overall, comments, content_type, player_job, ip_address, created_at,
+             deleted_at, is_published, duty)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+             ON CONFLICT (id) DO NOTHING",
+            &[
+                &feedback.id,
+                &feedback.character_name,
+                &feedback.server,
+                &feedback.is_anonymous,
+                &feedback.rating_mechanics,
+                &feedback.rating_damage,
+                &feedback.rating_teamwork,
+                &feedback.rating_communication,
+                &feedback.rating_overall,
+                &feedback.comments,
+                &feedback.content_type,
+                &feedback.player_job,
+                &feedback.ip_address,
+                &feedback.created_at,
+                &feedback.deleted_at,
+                &feedback.is_published,
+                &feedback.duty,
+            ],
+        )?;
+        if rows == 0 {
+            return Ok(false);
+        }
+        for tag in &feedback.tags {
+            self.add_tag(&feedback.id, tag)?;
+        }
+        Ok(true)
+    }
+
+    fn get(&self, id: &str) -> RepoResult<Option<Feedback>> {
+        let sql = format!("SELECT {FEEDBACK_COLUMNS} FROM feedback WHERE id = $1");
+        let row = self.0.borrow_mut().query_opt(sql.as_str(), &[&id])?;
+        Ok(row.as_ref().map(feedback_from_row))
+    }
+
+    fn list(&self, filter: &FeedbackFilter) -> RepoResult<Vec<Feedback>> {
+        let (trashed, order) = if filter.trashed {
+            ("IS NOT NULL", "deleted_at")
+        } else {
+            ("IS NULL", "created_at")
+        };
+        let sql = format!(
+            "SELECT {FEEDBACK_COLUMNS} FROM feedback WHERE deleted_at {trashed}
+               AND ($1::BOOLEAN IS NULL OR is_published = $1)
+               AND ($2::TEXT IS NULL OR id IN (SELECT feedback_id FROM feedback_tags WHERE tag = $2))
+             ORDER BY {order} DESC"
+        );
+        let tag = filter.tag.as_deref().filter(|v| !v.is_empty());
+        let rows = self
+            .0
+            .borrow_mut()
+            .query(sql.as_str(), &[&filter.published, &tag])?;
+        Ok(rows.iter().map(feedback_from_row).collect())
+    }
+
+    fn soft_delete(&self, id: &str) -> RepoResult<bool> {
+        let rows = self.execute(
+            "UPDATE feedback SET deleted_at = $1 WHERE id = $2 AND deleted_at IS NULL",
+            &[&now(), &id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn restore(&self, id: &str) -> RepoResult<bool> {
+        let rows = self.execute(
+            "UPDATE feedback SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL",
+            &[&id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn purge(&self, id: &str) -> RepoResult<bool> {
+        let rows = self.execute(
+            "DELETE FROM feedback WHERE id = $1 AND deleted_at IS NOT NULL",
+            &[&id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn purge_expired(&self, retention_days: i64) -> RepoResult<usize> {
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(retention_days))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        let rows = self.execute(
+            "DELETE FROM feedback WHERE deleted_at IS NOT NULL AND deleted_at < $1",
+            &[&cutoff],
+        )?;
+        Ok(rows as usize)
+    }
+
+    fn set_published(&self, id: &str, published: bool) -> RepoResult<bool> {
+        let rows = self.execute(
+            "UPDATE feedback SET is_published = $1 WHERE id = $2",
+            &[&published, &id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn add_tag(&self, id: &str, tag: &str) -> RepoResult<bool> {
+        if self.count("SELECT COUNT(*) FROM feedback WHERE id = $1", &[&id])? == 0 {
+            return Ok(false);
+        }
+        self.execute(
+            "INSERT INTO feedback_tags (feedback_id, tag) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            &[&id, &tag],
+        )?;
+        Ok(true)
+    }
+
+    fn stats(&self) -> RepoResult<FeedbackStats> {
+        let row = self.0.borrow_mut().query_one(
+            "SELECT
+                COUNT(*) FILTER (WHERE deleted_at IS NULL),
+                COUNT(*) FILTER (WHERE deleted_at IS NOT NULL),
+                COUNT(*) FILTER (WHERE deleted_at IS NULL AND is_published),
+                AVG(rating_overall) FILTER (WHERE deleted_at IS NULL)::DOUBLE PRECISION
+             FROM feedback",
+            &[],
+        )?;
+        let by_server = self
+            .0
+            .borrow_mut()
+            .query(
+                "SELECT server, COUNT(*) FROM feedback
+                 WHERE deleted_at IS NULL AND server IS NOT NULL
+                 GROUP BY server",
+                &[],
+            )?
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        let by_job = self
+            .0
+            .borrow_mut()
+            .query(
+                "SELECT player_job, COUNT(*), SUM(rating_overall) FROM feedback
+                 WHERE deleted_at IS NULL AND player_job IS NOT NULL
+                 GROUP BY player_job",
+                &[],
+            )?
+            .iter()
+            .map(|row| RatingTally {
+                key: row.get(0),
+                count: row.get(1),
+                overall_total: row.get(2),
+            })
+            .collect();
+        let by_duty = self
+            .0
+            .borrow_mut()
+            .query(
+                "SELECT duty, COUNT(*), SUM(rating_overall) FROM feedback
+                 WHERE deleted_at IS NULL AND duty IS NOT NULL
+                 GROUP BY duty",
+                &[],
+            )?
+            .iter()
+            .map(|row| RatingTally {
+                key: row.get(0),
+                count: row.get(1),
+                overall_total: row.get(2),
+            })
+            .collect();
+        Ok(FeedbackStats {
+            live: row.get(0),
+            trashed: row.get(1),
+            published: row.get(2),
+            banned_ips: self.count("SELECT COUNT(*) FROM banned_ips", &[])?,
+            average_overall: row.get(3),
+            by_server,
+            by_job,
+            by_duty,
+        })
+    }
+
+    fn ban_ip(&self, ip_address: &str) -> RepoResult<()> {
+        self.execute(
+            "INSERT INTO banned_ips (ip_address, banned_at) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            &[&ip_address, &now()],
+        )?;
+        Ok(())
+    }
+
+    fn is_ip_banned(&self, ip_address: &str) -> RepoResult<bool> {
+        let count = self.count(
+            "SELECT COUNT(*) FROM banned_ips WHERE ip_address = $1",
+            &[&ip_address],
+        )?;
+        Ok(count > 0)
+    }
+
+    fn check_rate_limits(
+        &self,
+        ip_address: &str,
+        cookie_id: &str,
+        ip_limit_max: i64,
+    ) -> RepoResult<Option<RateLimitType>> {
+        // Submissions and blocked attempts both count towards the IP hard limit
+        let cutoff = minutes_ago(IP_LIMIT_WINDOW_MINUTES);
+        let ip_count = self.count(
+            "SELECT (SELECT COUNT(*) FROM feedback WHERE ip_address = $1 AND created_at > $2)
+                  + (SELECT COUNT(*) FROM ip_attempts WHERE ip_address = $1 AND attempted_at > $2)",
+            &[&ip_address, &cutoff],
+        )?;
+        if ip_count >= ip_limit_max {
+            return Ok(Some(RateLimitType::IpHardLimit));
+        }
+
+        let cutoff = minutes_ago(COOKIE_LIMIT_WINDOW_MINUTES);
+        let cookie_count = self.count(
+            "SELECT COUNT(*) FROM cookie_submissions WHERE cookie_id = $1 AND submitted_at > $2",
+            &[&cookie_id, &cutoff],
+        )?;
+        if cookie_count > 0 {
+            return Ok(Some(RateLimitType::CookieSoftLimit));
+        }
+
+        Ok(None)
+    }
+
+    fn record_ip_attempt(&self, ip_address: &str) -> RepoResult<()> {
+        self.execute(
+            "INSERT INTO ip_attempts (ip_address, attempted_at) VALUES ($1, $2)",
+            &[&ip_address, &now()],
+        )?;
+        Ok(())
+    }
+
+    fn record_submission(&self, cookie_id: &str) -> RepoResult<()> {
+        self.execute(
+            "INSERT INTO cookie_submissions (cookie_id, submitted_at) VALUES ($1, $2)
+             ON CONFLICT (cookie_id) DO UPDATE SET submitted_at = EXCLUDED.submitted_at",
+            &[&cookie_id, &now()],
+        )?;
+        Ok(())
+    }
+
+    fn record_audit(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        action: AuditAction,
+        target: Option<&str>,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> RepoResult<()> {
+        self.execute(
+            "INSERT INTO audit_log (created_at, admin, ip_address, action, target, before, after)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            &[
+                &now(),
+                &admin,
+                &ip_address,
+                &action.as_str(),
+                &target,
+                &before,
+                &after,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn has_recent_login(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        window_minutes: i64,
+    ) -> RepoResult<bool> {
+        let count = self.count(
+            "SELECT COUNT(*) FROM audit_log
+             WHERE admin = $1 AND ip_address = $2 AND action = $3 AND created_at > $4",
+            &[
+                &admin,
+                &ip_address,
+                &AuditAction::LoginSuccess.as_str(),
+                &minutes_ago(window_minutes),
+            ],
+        )?;
+        Ok(count > 0)
+    }
+
+    fn list_audit(&self, filter: &AuditFilter, limit: i64) -> RepoResult<Vec<AuditEntry>> {
+        let set = |value: &Option<String>| value.clone().filter(|v| !v.is_empty());
+        let rows = self.0.borrow_mut().query(
+            "SELECT id, created_at, admin, ip_address, action, target, before, after
+             FROM audit_log
+             WHERE ($1::TEXT IS NULL OR action = $1)
+               AND ($2::TEXT IS NULL OR admin ILIKE '%' || $2 || '%')
+               AND ($3::TEXT IS NULL OR target ILIKE '%' || $3 || '%' OR ip_address ILIKE '%' || $3 || '%')
+             ORDER BY id DESC
+             LIMIT $4",
+            &[
+                &set(&filter.action),
+                &set(&filter.admin),
+                &set(&filter.search),
+                &limit,
+            ],
+        )?;
+        Ok(rows
+            .iter()
+            .map(|row| AuditEntry {
+                id: row.get(0),
+                created_at: row.get(1),
+                admin: row.get(2),
+                ip_address: row.get(3),
+                action: row.get(4),
+                target: row.get(5),
+                before: row.get(6),
+                after: row.get(7),
+            })
+            .collect())
+    }
+}
+
+/// These run against the server in `DATABASE_URL` and are skipped when it isn't set.
+/// Each test works in a transaction that is rolled back, but it locks the tables
+/// while it runs, so point it at a scratch database.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use postgres::Transaction;
+
+    fn with_repo(test: impl FnOnce(&PgRepository<'_, Transaction<'_>>)) {
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            eprintln!("DATABASE_URL not set, skipping PostgreSQL test");
+            return;
+        };
+        let mut client = Client::connect(&url, NoTls).expect("connect to DATABASE_URL");
+        migrate(&mut client).expect("migrate");
+        let mut tx = client.transaction().unwrap();
+        tx.batch_execute(
+            "TRUNCATE feedback, feedback_tags, banned_ips, cookie_submissions, ip_attempts, audit_log",
+        )
+        .unwrap();
+        test(&PgRepository::new(&mut tx));
+        // Dropping the transaction rolls everything back
+    }
+
+    fn feedback(id: &str, ip_address: &str, overall: i32) -> Feedback {
+        Feedback {
+            id: id.to_string(),
+            character_name: Some("Tester".to_string()),
+            server: Some("Gilgamesh".to_string()),
+            is_anonymous: false,
+            rating_mechanics: 3,
+            rating_damage: 3,
+            rating_teamwork: 3,
+            rating_communication: 3,
+            rating_overall: overall,
+            comments: None,
+            content_type: Some("Ultimate Raid".to_string()),
+            player_job: Some("PLD".to_string()),
+            duty: Some("Futures Rewritten (Ultimate)".to_string()),
+            ip_address: ip_address.to_string(),
+            created_at: now(),
+            deleted_at: None,
+            is_published: false,
+            tags: vec!["b".to_string(), "a".to_string()],
+        }
+    }
+
+    #[test]
+    fn insert_get_and_list() {
+        with_repo(|repo| {
+            let entry = feedback("one", "198.51.100.1", 4);
+            assert!(repo.insert(&entry).unwrap());
+            assert!(!repo.insert(&entry).unwrap());
+
+            let stored = repo.get("one").unwrap().unwrap();
+            assert_eq!(stored.tags, ["a", "b"]);
+            assert_eq!(
+                Feedback {
+                    tags: entry.tags.clone(),
+                    ..stored
+                },
+                entry
+            );
+            assert_eq!(repo.get("missing").unwrap(), None);
+
+            repo.insert(&feedback("two", "198.51.100.2", 2)).unwrap();
+            assert!(repo.set_published("two", true).unwrap());
+            assert!(repo.add_tag("one", "c").unwrap());
+            let published = FeedbackFilter {
+                published: Some(true),
+                ..FeedbackFilter::live()
+            };
+            let ids = |filter: &FeedbackFilter| -> Vec<String> {
+                repo.list(filter)
+                    .unwrap()
+                    .into_iter()
+                    .map(|f| f.id)
+                    .collect()
+            };
+            assert_eq!(ids(&published), ["two"]);
+            let tagged = FeedbackFilter {
+                tag: Some("c".to_string()),
+                ..FeedbackFilter::live()
+            };
+            assert_eq!(ids(&tagged), ["one"]);
+        });
+    }
+
+    #[test]
+    fn soft_delete_restore_and_purge() {
+        with_repo(|repo| {
+            repo.insert(&feedback("one", "198.51.100.1", 4)).unwrap();
+            assert!(!repo.purge("one").unwrap());
+
+            assert!(repo.soft_delete("one").unwrap());
+            assert!(!repo.soft_delete("one").unwrap());
+            assert!(repo.list(&FeedbackFilter::live()).unwrap().is_empty());
+            assert_eq!(repo.list(&FeedbackFilter::trash()).unwrap().len(), 1);
+
+            assert!(repo.restore("one").unwrap());
+            assert!(!repo.restore("one").unwrap());
+            assert_eq!(repo.list(&FeedbackFilter::live()).unwrap().len(), 1);
+
+            repo.soft_delete("one").unwrap();
+            assert_eq!(repo.purge_expired(30).unwrap(), 0);
+            assert!(repo.purge("one").unwrap());
+            assert_eq!(repo.get("one").unwrap(), None);
+
+            // Entries trashed long enough ago are purged automatically
+            let mut old = feedback("old", "198.51.100.1", 4);
+            old.deleted_at = Some(minutes_ago(31 * 24 * 60));
+            repo.insert(&old).unwrap();
+            assert_eq!(repo.purge_expired(30).unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn rate_limits_and_bans() {
+        with_repo(|repo| {
+            let ip = "198.51.100.1";
+            assert_eq!(repo.check_rate_limits(ip, "device-a", 2).unwrap(), None);
+
+            repo.insert(&feedback("one", ip, 4)).unwrap();
+            repo.record_submission("device-a").unwrap();
+            assert_eq!(
+                repo.check_rate_limits(ip, "device-a", 2).unwrap(),
+                Some(RateLimitType::CookieSoftLimit)
+            );
+            assert_eq!(repo.check_rate_limits(ip, "device-b", 2).unwrap(), None);
+
+            repo.record_ip_attempt(ip).unwrap();
+            assert_eq!(
+                repo.check_rate_limits(ip, "device-b", 2).unwrap(),
+                Some(RateLimitType::IpHardLimit)
+            );
+            assert_eq!(
+                repo.check_rate_limits("198.51.100.2", "device-c", 2)
+                    .unwrap(),
+                None
+            );
+
+            assert!(!repo.is_ip_banned(ip).unwrap());
+            repo.ban_ip(ip).unwrap();
+            repo.ban_ip(ip).unwrap();
+            assert!(repo.is_ip_banned(ip).unwrap());
+        });
+    }
+
+    #[test]
+    fn stats_count_live_feedback() {
+        with_repo(|repo| {
+            repo.insert(&feedback("one", "198.51.100.1", 2)).unwrap();
+            repo.insert(&feedback("two", "198.51.100.2", 4)).unwrap();
+            repo.insert(&feedback("three", "198.51.100.3", 5)).unwrap();
+            repo.set_published("two", true).unwrap();
+            repo.soft_delete("three").unwrap();
+            repo.ban_ip("198.51.100.3").unwrap();
+
+            let stats = repo.stats().unwrap();
+            assert_eq!(stats.live, 2);
+            assert_eq!(stats.trashed, 1);
+            assert_eq!(stats.published, 1);
+            assert_eq!(stats.banned_ips, 1);
+            assert_eq!(stats.average_overall, Some(3.0));
+            assert_eq!(stats.by_server, [("Gilgamesh".to_string(), 2)]);
+            let tally = RatingTally {
+                key: String::new(),
+                count: 2,
+                overall_total: 6,
+            };
+            assert_eq!(
+                stats.by_job,
+                [RatingTally {
+                    key: "PLD".to_string(),
+                    ..tally.clone()
+                }]
+            );
+            assert_eq!(
+                stats.by_duty,
+                [RatingTally {
+                    key: "Futures Rewritten (Ultimate)".to_string(),
+                    ..tally
+                }]
+            );
+        });
+    }
+}
@@ -0,0 +1,127 @@
+use parking_lot::{Mutex, MutexGuard};
+use rusqlite::{Connection, OpenFlags};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::db;
+
+/// Read-only connections opened alongside the writer. WAL mode lets them read while
+/// a write is in progress.
+const READER_CONNECTIONS: usize = 4;
+
+// How long a connection waits for a lock held by another one before giving up
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum DbError {
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Error),
+    /// The blocking task running the query panicked or was cancelled
+    Task(String),
+    /// The configuration asks for a backend this build doesn't include
+    Unsupported(&'static str),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Sqlite(e) => write!(f, "{e}"),
+            #[cfg(feature = "postgres")]
+            DbError::Postgres(e) => write!(f, "{e}"),
+            DbError::Task(e) => write!(f, "database task failed: {e}"),
+            DbError::Unsupported(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sqlite(e)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<postgres::Error> for DbError {
+    fn from(e: postgres::Error) -> Self {
+        DbError::Postgres(e)
+    }
+}
+
+struct Connections {
+    writer: Mutex<Connection>,
+    readers: Vec<Mutex<Connection>>,
+    next_reader: AtomicUsize,
+}
+
+/// SQLite connections shared by the server: one writer and several readers. Queries
+/// run on tokio's blocking thread pool through `read` and `write`, so SQLite I/O never
+/// stalls the async workers.
+#[derive(Clone)]
+pub struct DbPool(Arc<Connections>);
+
+impl DbPool {
+    /// Open the database, applying migrations through the writer connection first
+    pub fn open(db_path: &str) -> rusqlite::Result<DbPool> {
+        let writer = db::init_database(db_path)?;
+        writer.busy_timeout(BUSY_TIMEOUT)?;
+
+        let readers = (0..READER_CONNECTIONS)
+            .map(|_| {
+                let conn = Connection::open_with_flags(
+                    db_path,
+                    OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )?;
+                conn.busy_timeout(BUSY_TIMEOUT)?;
+                Ok(Mutex::new(conn))
+            })
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(DbPool(Arc::new(Connections {
+            writer: Mutex::new(writer),
+            readers,
+            next_reader: AtomicUsize::new(0),
+        })))
+    }
+
+    /// Lock a reader, preferring an idle one. Blocks, so only call from a blocking thread.
+    pub fn reader(&self) -> MutexGuard<'_, Connection> {
+        let readers = &self.0.readers;
+        let start = self.0.next_reader.fetch_add(1, Ordering::Relaxed);
+        (0..readers.len())
+            .find_map(|i| readers[(start + i) % readers.len()].try_lock())
+            .unwrap_or_else(|| readers[start % readers.len()].lock())
+    }
+
+    /// Lock the writer. Blocks, so only call from a blocking thread.
+    pub fn writer(&self) -> MutexGuard<'_, Connection> {
+        self.0.writer.lock()
+    }
+
+    /// Run queries that only read on a reader connection
+    pub async fn read<T, F>(&self, f: F) -> Result<T, DbError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, DbError> + Send + 'static,
+    {
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || f(&pool.reader()))
+            .await
+            .map_err(|e| DbError::Task(e.to_string()))?
+    }
+
+    /// Run queries that write on the single writer connection. Takes `&mut` so the
+    /// closure can open a transaction.
+    pub async fn write<T, F>(&self, f: F) -> Result<T, DbError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, DbError> + Send + 'static,
+    {
+        let pool = self.clone();
+        tokio::task::spawn_blocking(move || f(&mut pool.writer()))
+            .await
+            .map_err(|e| DbError::Task(e.to_string()))?
+    }
+}
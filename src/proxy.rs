@@ -0,0 +1,359 @@
+use actix_web::http::header::HeaderMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// A single trusted proxy entry, either a bare address or a CIDR range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpCidr {
+    /// Parse "10.0.0.1", "10.0.0.0/8", "::1" or "fd00::/8"
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        let (addr_str, prefix_str) = match input.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (input, None),
+        };
+
+        let addr: IpAddr = addr_str.trim().parse().ok()?;
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match prefix_str {
+            Some(p) => p.trim().parse::<u8>().ok().filter(|&len| len <= max_len)?,
+            None => max_len,
+        };
+
+        Some(IpCidr { addr, prefix_len })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Compare IPv4-mapped IPv6 addresses (::ffff:a.b.c.d) as plain IPv4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// The set of proxies whose forwarding headers we are willing to believe
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<IpCidr>,
+}
+
+impl TrustedProxies {
+    /// Parse a comma-separated list of addresses and CIDR ranges.
+    /// Returns the parsed set along with any entries that could not be parsed.
+    pub fn parse_list(list: &str) -> (Self, Vec<String>) {
+        let mut ranges = Vec::new();
+        let mut invalid = Vec::new();
+
+        for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match IpCidr::parse(entry) {
+                Some(range) => ranges.push(range),
+                None => invalid.push(entry.to_string()),
+            }
+        }
+
+        (TrustedProxies { ranges }, invalid)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    pub fn describe(&self) -> String {
+        self.ranges
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Parse a single hop from a forwarding header.
+/// Accepts bare addresses, "1.2.3.4:port", "[::1]" and "[::1]:port".
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim().trim_matches('"');
+
+    if let Some(rest) = hop.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+
+    if let Ok(ip) = hop.parse::<IpAddr>() {
+        return Some(ip);
+    }
+
+    hop.parse::<SocketAddr>().ok().map(|addr| addr.ip())
+}
+
+/// Collect the `for=` values of every RFC 7239 `Forwarded` header, in order
+fn forwarded_chain(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all("Forwarded")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("for")
+                    .then(|| value.trim().to_string())
+            })
+        })
+        .collect()
+}
+
+/// Collect every hop listed in `X-Forwarded-For` headers, in order
+fn x_forwarded_for_chain(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().to_string())
+        .filter(|hop| !hop.is_empty())
+        .collect()
+}
+
+/// Work out the real client address for a request.
+///
+/// Forwarding headers are only consulted when the peer is a trusted proxy. The
+/// chain is then walked from the right (closest hop) and the first address that
+/// isn't itself a trusted proxy is taken as the client. Hops we cannot parse
+/// (e.g. "unknown" or obfuscated identifiers) stop the walk, and the last address
+/// we could verify is used instead so a client can never pick its own identity.
+pub fn resolve_client_ip(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted: &TrustedProxies,
+) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted.contains(peer) {
+        return Some(peer);
+    }
+
+    let mut chain = forwarded_chain(headers);
+    if chain.is_empty() {
+        chain = x_forwarded_for_chain(headers);
+    }
+    if chain.is_empty() {
+        if let Some(real_ip) = headers
+            .get("X-Real-IP")
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_hop)
+        {
+            return Some(real_ip);
+        }
+    }
+
+    let mut client = peer;
+    for hop in chain.iter().rev() {
+        match parse_hop(hop) {
+            Some(ip) => {
+                client = ip;
+                if !trusted.contains(ip) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            );
+        }
+        map
+    }
+
+    fn trusted(list: &str) -> TrustedProxies {
+        let (proxies, invalid) = TrustedProxies::parse_list(list);
+        assert!(invalid.is_empty(), "invalid entries {invalid:?}");
+        proxies
+    }
+
+    #[test]
+    fn cidr_matches_v4_ranges() {
+        let range = IpCidr::parse("10.0.0.0/8").unwrap();
+        assert!(range.contains(ip("10.1.2.3")));
+        assert!(!range.contains(ip("11.0.0.1")));
+        assert!(!range.contains(ip("::1")));
+    }
+
+    #[test]
+    fn cidr_matches_v6_ranges() {
+        let range = IpCidr::parse("fd00::/8").unwrap();
+        assert!(range.contains(ip("fd12:3456::1")));
+        assert!(!range.contains(ip("fe80::1")));
+        assert!(!range.contains(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn cidr_zero_prefix_matches_everything_in_its_family() {
+        assert!(IpCidr::parse("0.0.0.0/0")
+            .unwrap()
+            .contains(ip("203.0.113.9")));
+        assert!(IpCidr::parse("::/0").unwrap().contains(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn cidr_bare_address_matches_only_itself() {
+        let range = IpCidr::parse(" 192.0.2.1 ").unwrap();
+        assert_eq!(range.to_string(), "192.0.2.1/32");
+        assert!(range.contains(ip("192.0.2.1")));
+        assert!(!range.contains(ip("192.0.2.2")));
+        assert_eq!(IpCidr::parse("::1").unwrap().to_string(), "::1/128");
+    }
+
+    #[test]
+    fn cidr_rejects_bad_input() {
+        assert_eq!(IpCidr::parse("10.0.0.0/33"), None);
+        assert_eq!(IpCidr::parse("::/129"), None);
+        assert_eq!(IpCidr::parse("10.0.0.0/x"), None);
+        assert_eq!(IpCidr::parse("not-an-ip"), None);
+        let (proxies, invalid) = TrustedProxies::parse_list("10.0.0.1, nope, ::1");
+        assert_eq!(invalid, ["nope"]);
+        assert_eq!(proxies.describe(), "10.0.0.1/32, ::1/128");
+    }
+
+    #[test]
+    fn cidr_treats_ipv4_mapped_peers_as_ipv4() {
+        let range = IpCidr::parse("127.0.0.0/8").unwrap();
+        assert!(range.contains(ip("::ffff:127.0.0.1")));
+        assert!(!range.contains(ip("::ffff:10.0.0.1")));
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_forwarding_headers() {
+        let headers = headers(&[
+            ("x-forwarded-for", "1.1.1.1"),
+            ("forwarded", "for=2.2.2.2"),
+            ("x-real-ip", "3.3.3.3"),
+        ]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("203.0.113.5")), &headers, &trusted("10.0.0.0/8")),
+            Some(ip("203.0.113.5"))
+        );
+        assert_eq!(
+            resolve_client_ip(
+                Some(ip("203.0.113.5")),
+                &headers,
+                &TrustedProxies::default()
+            ),
+            Some(ip("203.0.113.5"))
+        );
+    }
+
+    #[test]
+    fn takes_rightmost_untrusted_hop_through_trusted_chain() {
+        // The client claims 6.6.6.6, but only the hop added by our own proxies counts
+        let headers = headers(&[
+            ("x-forwarded-for", "6.6.6.6, 198.51.100.7"),
+            ("x-forwarded-for", "10.0.0.3, 10.0.0.2"),
+        ]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted("10.0.0.0/8")),
+            Some(ip("198.51.100.7"))
+        );
+    }
+
+    #[test]
+    fn all_trusted_chain_resolves_to_leftmost_hop() {
+        let headers = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted("10.0.0.0/8")),
+            Some(ip("10.0.0.3"))
+        );
+    }
+
+    #[test]
+    fn forwarded_takes_precedence_over_x_forwarded_for() {
+        let headers = headers(&[
+            ("x-forwarded-for", "198.51.100.1"),
+            ("forwarded", "for=198.51.100.2;proto=https, for=10.0.0.2"),
+        ]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted("10.0.0.0/8")),
+            Some(ip("198.51.100.2"))
+        );
+    }
+
+    #[test]
+    fn parses_quoted_v6_hops_with_ports() {
+        let forwarded = headers(&[("forwarded", "for=\"[2001:db8::7]:4711\"")]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("::1")), &forwarded, &trusted("::1")),
+            Some(ip("2001:db8::7"))
+        );
+        let x_forwarded_for = headers(&[("x-forwarded-for", "198.51.100.9:5000")]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("::1")), &x_forwarded_for, &trusted("::1")),
+            Some(ip("198.51.100.9"))
+        );
+    }
+
+    #[test]
+    fn unknown_hop_stops_the_walk() {
+        // Nothing left of "unknown" can be verified, so the last trusted hop is used
+        let headers = headers(&[("forwarded", "for=198.51.100.3, for=unknown, for=10.0.0.2")]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted("10.0.0.0/8")),
+            Some(ip("10.0.0.2"))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_x_real_ip() {
+        let headers = headers(&[("x-real-ip", "198.51.100.4")]);
+        assert_eq!(
+            resolve_client_ip(Some(ip("10.0.0.1")), &headers, &trusted("10.0.0.0/8")),
+            Some(ip("198.51.100.4"))
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use crate::config::{read_env_file, Config, EnvFile, LiveSettings, SharedSettings};
+
+// How often the config file and .env are checked for changes
+const WATCH_INTERVAL_SECS: u64 = 5;
+
+/// Where settings are re-read from on reload
+pub struct ReloadSources {
+    config_path: Option<PathBuf>,
+    env_path: Option<PathBuf>,
+    /// The configuration the server started with, to spot changes that need a restart
+    startup: Config,
+}
+
+impl ReloadSources {
+    pub fn new(config_path: Option<PathBuf>, env_path: Option<PathBuf>, startup: Config) -> Self {
+        ReloadSources {
+            config_path,
+            env_path,
+            startup,
+        }
+    }
+
+    /// Warn about settings that changed on disk but are only read at startup
+    fn warn_restart_required(&self, config: &Config) {
+        let startup = &self.startup;
+        let mut needs_restart = Vec::new();
+        if config.host != startup.host || config.port != startup.port {
+            needs_restart.push("listen address");
+        }
+        if config.database_path != startup.database_path
+            || config.database_url != startup.database_url
+        {
+            needs_restart.push("database path");
+        }
+        if config.backup_dir != startup.backup_dir
+            || config.backup_interval_hours != startup.backup_interval_hours
+            || config.backup_keep != startup.backup_keep
+        {
+            needs_restart.push("backups");
+        }
+        if config.assets_dir != startup.assets_dir || config.uploads_dir != startup.uploads_dir {
+            needs_restart.push("asset directories");
+        }
+        if config.admin_password != startup.admin_password {
+            needs_restart.push("admin password");
+        }
+        if config.rate_limit_minutes != startup.rate_limit_minutes
+            || config.ip_rate_limit_max != startup.ip_rate_limit_max
+        {
+            needs_restart.push("rate limits");
+        }
+        if config.trash_retention_days != startup.trash_retention_days {
+            needs_restart.push("trash retention");
+        }
+        if config.trusted_proxies.describe() != startup.trusted_proxies.describe() {
+            needs_restart.push("trusted proxies");
+        }
+
+        if !needs_restart.is_empty() {
+            log::warn!(
+                "Changes to {} only take effect after a restart",
+                needs_restart.join(", ")
+            );
+        }
+    }
+
+    /// Re-read the configuration and swap in the new live settings. Invalid
+    /// configuration is reported and the running settings are left untouched.
+    pub fn reload(&self, settings: &SharedSettings, trigger: &str) {
+        log::info!("Reloading configuration ({trigger})");
+
+        // The real environment still wins over .env, as it did at startup
+        let env_file = match &self.env_path {
+            Some(path) => match read_env_file(path) {
+                Ok(env_file) => env_file,
+                Err(e) => {
+                    log::error!("Failed to read .env, keeping current settings: {e}");
+                    return;
+                }
+            },
+            None => EnvFile::new(),
+        };
+        let config = match Config::load_with_env_file(self.config_path.as_deref(), &env_file) {
+            Ok(config) => config,
+            Err(e) => {
+                log::error!("{e}");
+                log::error!("Reload aborted, keeping current settings");
+                return;
+            }
+        };
+
+        self.warn_restart_required(&config);
+
+        // Values saved from the settings page keep overriding the reloaded defaults
+        let mut changed = Vec::new();
+        settings.update(|current| {
+            let new = config.live_settings(current.player_overrides.clone());
+            changed = describe_changes(current, &new);
+            new
+        });
+
+        if changed.is_empty() {
+            log::info!("Configuration reloaded, no changes");
+        } else {
+            log::info!("Configuration reloaded, changed: {}", changed.join(", "));
+        }
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        self.config_path
+            .iter()
+            .chain(self.env_path.iter())
+            .cloned()
+            .chain(self.startup.worlds_file.iter().map(PathBuf::from))
+            .collect()
+    }
+}
+
+fn describe_changes(old: &LiveSettings, new: &LiveSettings) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    if old.player.name != new.player.name {
+        changed.push("player name");
+    }
+    if old.player.server != new.player.server || old.player.datacenter != new.player.datacenter {
+        changed.push("player world");
+    }
+    if old.player.banner_image != new.player.banner_image
+        || old.player.profile_image != new.player.profile_image
+    {
+        changed.push("images");
+    }
+    if old.player.tagline != new.player.tagline {
+        changed.push("tagline");
+    }
+    if old.filter_words != new.filter_words {
+        changed.push("filter words");
+    }
+    if old.discord_webhook_url != new.discord_webhook_url {
+        changed.push("Discord webhook");
+    }
+    if old.security != new.security {
+        changed.push("security headers");
+    }
+    if old.worlds != new.worlds {
+        changed.push("world list");
+    }
+    changed
+}
+
+fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Reload off the async runtime, since it reads files and parses them
+async fn reload_blocking(
+    sources: &Arc<ReloadSources>,
+    settings: &SharedSettings,
+    trigger: &'static str,
+) {
+    let sources = sources.clone();
+    let settings = settings.clone();
+    if let Err(e) = tokio::task::spawn_blocking(move || sources.reload(&settings, trigger)).await {
+        log::error!("Reload failed: {e}");
+    }
+}
+
+/// Reload on SIGHUP, and whenever the config file, .env or world list changes on disk
+pub fn spawn_watchers(sources: Arc<ReloadSources>, settings: SharedSettings) {
+    #[cfg(unix)]
+    {
+        let sources = sources.clone();
+        let settings = settings.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    log::error!("Failed to listen for SIGHUP: {e}");
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                reload_blocking(&sources, &settings, "SIGHUP").await;
+            }
+        });
+    }
+
+    let watched = sources.watched_files();
+    if watched.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut last_modified = modified_times(&watched);
+        let mut interval = tokio::time::interval(Duration::from_secs(WATCH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let modified = modified_times(&watched);
+            if modified != last_modified {
+                last_modified = modified;
+                reload_blocking(&sources, &settings, "file changed").await;
+            }
+        }
+    });
+}
@@ -0,0 +1,501 @@
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+
+use crate::db::{self, RateLimitType, COOKIE_LIMIT_WINDOW_MINUTES, IP_LIMIT_WINDOW_MINUTES};
+use crate::models::{
+    AuditAction, AuditEntry, AuditFilter, Feedback, FeedbackFilter, FeedbackStats, RatingTally,
+};
+use crate::pool::DbError;
+
+pub type RepoResult<T> = Result<T, DbError>;
+
+/// Storage for feedback, bans, rate-limit counters and the audit trail of changes to
+/// them. Handler logic is written against this rather than SQL, so it can run on any
+/// backend.
+///
+/// Calls block, so async code reaches a backend through `FeedbackStore`. Changes that
+/// must be atomic are made on a transaction, which implements this trait too.
+pub trait FeedbackRepository {
+    /// Store a feedback entry with its tags. Returns false if one with the same id
+    /// already exists.
+    fn insert(&self, feedback: &Feedback) -> RepoResult<bool>;
+
+    fn get(&self, id: &str) -> RepoResult<Option<Feedback>>;
+
+    fn list(&self, filter: &FeedbackFilter) -> RepoResult<Vec<Feedback>>;
+
+    /// Move feedback to the trash bin. Returns false if no live entry matched.
+    fn soft_delete(&self, id: &str) -> RepoResult<bool>;
+
+    /// Bring feedback back out of the trash bin. Returns false if it wasn't trashed.
+    fn restore(&self, id: &str) -> RepoResult<bool>;
+
+    /// Permanently delete feedback. Only entries already in the trash can be purged.
+    fn purge(&self, id: &str) -> RepoResult<bool>;
+
+    /// Permanently delete everything that has been in the trash longer than
+    /// `retention_days`, returning how many entries were removed
+    fn purge_expired(&self, retention_days: i64) -> RepoResult<usize>;
+
+    /// Returns false if the feedback doesn't exist
+    fn set_published(&self, id: &str, published: bool) -> RepoResult<bool>;
+
+    /// Returns false if the feedback doesn't exist
+    fn add_tag(&self, id: &str, tag: &str) -> RepoResult<bool>;
+
+    fn stats(&self) -> RepoResult<FeedbackStats>;
+
+    fn ban_ip(&self, ip_address: &str) -> RepoResult<()>;
+
+    fn is_ip_banned(&self, ip_address: &str) -> RepoResult<bool>;
+
+    /// The limit a new submission from this IP and device would hit, if any
+    fn check_rate_limits(
+        &self,
+        ip_address: &str,
+        cookie_id: &str,
+        ip_limit_max: i64,
+    ) -> RepoResult<Option<RateLimitType>>;
+
+    /// Count a rejected submission towards the IP's hard limit
+    fn record_ip_attempt(&self, ip_address: &str) -> RepoResult<()>;
+
+    /// Start the device's soft limit window
+    fn record_submission(&self, cookie_id: &str) -> RepoResult<()>;
+
+    /// Record an admin action. `before` and `after` are JSON snapshots.
+    fn record_audit(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        action: AuditAction,
+        target: Option<&str>,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> RepoResult<()>;
+
+    /// Whether `admin` already has a successful login from `ip_address` within the
+    /// window. Used to log one login per session rather than one per Basic-auth request.
+    fn has_recent_login(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        window_minutes: i64,
+    ) -> RepoResult<bool>;
+
+    /// Most recent audit entries first, narrowed by any filters that are set
+    fn list_audit(&self, filter: &AuditFilter, limit: i64) -> RepoResult<Vec<AuditEntry>>;
+}
+
+/// The SQLite backend. A `Transaction` derefs to its `Connection`, so everything done
+/// through `&*tx` commits or rolls back together.
+impl FeedbackRepository for Connection {
+    fn insert(&self, feedback: &Feedback) -> RepoResult<bool> {
+        Ok(db::insert_feedback(self, feedback)?)
+    }
+
+    fn get(&self, id: &str) -> RepoResult<Option<Feedback>> {
+        Ok(db::get_feedback(self, id)?)
+    }
+
+    fn list(&self, filter: &FeedbackFilter) -> RepoResult<Vec<Feedback>> {
+        Ok(db::list_feedback(self, filter)?)
+    }
+
+    fn soft_delete(&self, id: &str) -> RepoResult<bool> {
+        Ok(db::soft_delete_feedback(self, id)?)
+    }
+
+    fn restore(&self, id: &str) -> RepoResult<bool> {
+        Ok(db::restore_feedback(self, id)?)
+    }
+
+    fn purge(&self, id: &str) -> RepoResult<bool> {
+        Ok(db::purge_feedback(self, id)?)
+    }
+
+    fn purge_expired(&self, retention_days: i64) -> RepoResult<usize> {
+        Ok(db::purge_expired_trash(self, retention_days)?)
+    }
+
+    fn set_published(&self, id: &str, published: bool) -> RepoResult<bool> {
+        Ok(db::set_published(self, id, published)?)
+    }
+
+    fn add_tag(&self, id: &str, tag: &str) -> RepoResult<bool> {
+        Ok(db::add_tag(self, id, tag)?)
+    }
+
+    fn stats(&self) -> RepoResult<FeedbackStats> {
+        Ok(db::feedback_stats(self)?)
+    }
+
+    fn ban_ip(&self, ip_address: &str) -> RepoResult<()> {
+        Ok(db::ban_ip(self, ip_address)?)
+    }
+
+    fn is_ip_banned(&self, ip_address: &str) -> RepoResult<bool> {
+        Ok(db::is_ip_banned(self, ip_address)?)
+    }
+
+    fn check_rate_limits(
+        &self,
+        ip_address: &str,
+        cookie_id: &str,
+        ip_limit_max: i64,
+    ) -> RepoResult<Option<RateLimitType>> {
+        Ok(db::check_rate_limits(
+            self,
+            ip_address,
+            cookie_id,
+            ip_limit_max,
+        )?)
+    }
+
+    fn record_ip_attempt(&self, ip_address: &str) -> RepoResult<()> {
+        Ok(db::record_ip_attempt(self, ip_address)?)
+    }
+
+    fn record_submission(&self, cookie_id: &str) -> RepoResult<()> {
+        Ok(db::record_submission(self, cookie_id)?)
+    }
+
+    fn record_audit(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        action: AuditAction,
+        target: Option<&str>,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> RepoResult<()> {
+        Ok(db::record_audit(
+            self, admin, ip_address, action, target, before, after,
+        )?)
+    }
+
+    fn has_recent_login(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        window_minutes: i64,
+    ) -> RepoResult<bool> {
+        Ok(db::has_recent_login(
+            self,
+            admin,
+            ip_address,
+            window_minutes,
+        )?)
+    }
+
+    fn list_audit(&self, filter: &AuditFilter, limit: i64) -> RepoResult<Vec<AuditEntry>> {
+        Ok(db::list_audit(self, filter, limit)?)
+    }
+}
+
+fn timestamp(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn minutes_ago(minutes: i64) -> String {
+    timestamp(chrono::Utc::now() - chrono::Duration::minutes(minutes))
+}
+
+#[derive(Default)]
+struct MemoryState {
+    feedback: HashMap<String, Feedback>,
+    banned_ips: HashSet<String>,
+    ip_attempts: Vec<(String, String)>,
+    cookie_submissions: HashMap<String, String>,
+    audit_log: Vec<AuditEntry>,
+}
+
+/// A backend that keeps everything in memory, for exercising handler logic without a
+/// database file. Nothing is persisted and there are no transactions, each call is
+/// applied on its own.
+#[derive(Default)]
+pub struct MemoryRepository {
+    state: Mutex<MemoryState>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        MemoryRepository::default()
+    }
+
+    /// Audit entries recorded so far, oldest first
+    pub fn audit_log(&self) -> Vec<AuditEntry> {
+        self.state.lock().audit_log.clone()
+    }
+}
+
+impl FeedbackRepository for MemoryRepository {
+    fn insert(&self, feedback: &Feedback) -> RepoResult<bool> {
+        let mut state = self.state.lock();
+        if state.feedback.contains_key(&feedback.id) {
+            return Ok(false);
+        }
+        let mut feedback = feedback.clone();
+        feedback.tags.sort();
+        feedback.tags.dedup();
+        state.feedback.insert(feedback.id.clone(), feedback);
+        Ok(true)
+    }
+
+    fn get(&self, id: &str) -> RepoResult<Option<Feedback>> {
+        Ok(self.state.lock().feedback.get(id).cloned())
+    }
+
+    fn list(&self, filter: &FeedbackFilter) -> RepoResult<Vec<Feedback>> {
+        let state = self.state.lock();
+        let tag = filter.tag.as_deref().filter(|tag| !tag.is_empty());
+        let mut feedbacks: Vec<Feedback> = state
+            .feedback
+            .values()
+            .filter(|f| f.deleted_at.is_some() == filter.trashed)
+            .filter(|f| filter.published.is_none_or(|p| f.is_published == p))
+            .filter(|f| tag.is_none_or(|tag| f.tags.iter().any(|t| t == tag)))
+            .cloned()
+            .collect();
+        if filter.trashed {
+            feedbacks.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        } else {
+            feedbacks.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        }
+        Ok(feedbacks)
+    }
+
+    fn soft_delete(&self, id: &str) -> RepoResult<bool> {
+        let mut state = self.state.lock();
+        match state.feedback.get_mut(id) {
+            Some(feedback) if feedback.deleted_at.is_none() => {
+                feedback.deleted_at = Some(timestamp(chrono::Utc::now()));
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn restore(&self, id: &str) -> RepoResult<bool> {
+        let mut state = self.state.lock();
+        match state.feedback.get_mut(id) {
+            Some(feedback) if feedback.deleted_at.is_some() => {
+                feedback.deleted_at = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn purge(&self, id: &str) -> RepoResult<bool> {
+        let mut state = self.state.lock();
+        if state
+            .feedback
+            .get(id)
+            .is_some_and(|f| f.deleted_at.is_some())
+        {
+            state.feedback.remove(id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn purge_expired(&self, retention_days: i64) -> RepoResult<usize> {
+        let cutoff = timestamp(chrono::Utc::now() - chrono::Duration::days(retention_days));
+        let mut state = self.state.lock();
+        let before = state.feedback.len();
+        state.feedback.retain(|_, f| {
+            f.deleted_at
+                .as_ref()
+                .is_none_or(|deleted| *deleted >= cutoff)
+        });
+        Ok(before - state.feedback.len())
+    }
+
+    fn set_published(&self, id: &str, published: bool) -> RepoResult<bool> {
+        let mut state = self.state.lock();
+        match state.feedback.get_mut(id) {
+            Some(feedback) => {
+                feedback.is_published = published;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn add_tag(&self, id: &str, tag: &str) -> RepoResult<bool> {
+        let mut state = self.state.lock();
+        let Some(feedback) = state.feedback.get_mut(id) else {
+            return Ok(false);
+        };
+        if !feedback.tags.iter().any(|t| t == tag) {
+            feedback.tags.push(tag.to_string());
+            feedback.tags.sort();
+        }
+        Ok(true)
+    }
+
+    fn stats(&self) -> RepoResult<FeedbackStats> {
+        let state = self.state.lock();
+        let live: Vec<&Feedback> = state
+            .feedback
+            .values()
+            .filter(|f| f.deleted_at.is_none())
+            .collect();
+        let average_overall = (!live.is_empty())
+            .then(|| live.iter().map(|f| f.rating_overall as f64).sum::<f64>() / live.len() as f64);
+        let mut by_server: Vec<(String, i64)> = Vec::new();
+        for server in live.iter().filter_map(|f| f.server.as_ref()) {
+            match by_server.iter_mut().find(|(name, _)| name == server) {
+                Some((_, count)) => *count += 1,
+                None => by_server.push((server.clone(), 1)),
+            }
+        }
+
+        Ok(FeedbackStats {
+            live: live.len() as i64,
+            trashed: (state.feedback.len() - live.len()) as i64,
+            published: live.iter().filter(|f| f.is_published).count() as i64,
+            banned_ips: state.banned_ips.len() as i64,
+            average_overall,
+            by_server,
+            by_job: RatingTally::tally(
+                live.iter()
+                    .filter_map(|f| Some((f.player_job.as_deref()?, f.rating_overall))),
+            ),
+            by_duty: RatingTally::tally(
+                live.iter()
+                    .filter_map(|f| Some((f.duty.as_deref()?, f.rating_overall))),
+            ),
+        })
+    }
+
+    fn ban_ip(&self, ip_address: &str) -> RepoResult<()> {
+        self.state.lock().banned_ips.insert(ip_address.to_string());
+        Ok(())
+    }
+
+    fn is_ip_banned(&self, ip_address: &str) -> RepoResult<bool> {
+        Ok(self.state.lock().banned_ips.contains(ip_address))
+    }
+
+    fn check_rate_limits(
+        &self,
+        ip_address: &str,
+        cookie_id: &str,
+        ip_limit_max: i64,
+    ) -> RepoResult<Option<RateLimitType>> {
+        let state = self.state.lock();
+
+        let cutoff = minutes_ago(IP_LIMIT_WINDOW_MINUTES);
+        let submissions = state
+            .feedback
+            .values()
+            .filter(|f| f.ip_address == ip_address && f.created_at > cutoff)
+            .count();
+        let attempts = state
+            .ip_attempts
+            .iter()
+            .filter(|(ip, at)| ip == ip_address && *at > cutoff)
+            .count();
+        if (submissions + attempts) as i64 >= ip_limit_max {
+            return Ok(Some(RateLimitType::IpHardLimit));
+        }
+
+        let cutoff = minutes_ago(COOKIE_LIMIT_WINDOW_MINUTES);
+        if state
+            .cookie_submissions
+            .get(cookie_id)
+            .is_some_and(|at| *at > cutoff)
+        {
+            return Ok(Some(RateLimitType::CookieSoftLimit));
+        }
+
+        Ok(None)
+    }
+
+    fn record_ip_attempt(&self, ip_address: &str) -> RepoResult<()> {
+        self.state
+            .lock()
+            .ip_attempts
+            .push((ip_address.to_string(), timestamp(chrono::Utc::now())));
+        Ok(())
+    }
+
+    fn record_submission(&self, cookie_id: &str) -> RepoResult<()> {
+        self.state
+            .lock()
+            .cookie_submissions
+            .insert(cookie_id.to_string(), timestamp(chrono::Utc::now()));
+        Ok(())
+    }
+
+    fn record_audit(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        action: AuditAction,
+        target: Option<&str>,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> RepoResult<()> {
+        let mut state = self.state.lock();
+        let id = state.audit_log.len() as i64 + 1;
+        state.audit_log.push(AuditEntry {
+            id,
+            created_at: timestamp(chrono::Utc::now()),
+            admin: admin.to_string(),
+            ip_address: ip_address.to_string(),
+            action: action.as_str().to_string(),
+            target: target.map(str::to_string),
+            before: before.map(str::to_string),
+            after: after.map(str::to_string),
+        });
+        Ok(())
+    }
+
+    fn has_recent_login(
+        &self,
+        admin: &str,
+        ip_address: &str,
+        window_minutes: i64,
+    ) -> RepoResult<bool> {
+        let cutoff = minutes_ago(window_minutes);
+        Ok(self.state.lock().audit_log.iter().any(|entry| {
+            entry.admin == admin
+                && entry.ip_address == ip_address
+                && entry.action == AuditAction::LoginSuccess.as_str()
+                && entry.created_at > cutoff
+        }))
+    }
+
+    fn list_audit(&self, filter: &AuditFilter, limit: i64) -> RepoResult<Vec<AuditEntry>> {
+        // Substring matches ignore case, like SQL's LIKE
+        let set = |value: &Option<String>| {
+            value
+                .as_deref()
+                .filter(|v| !v.is_empty())
+                .map(str::to_lowercase)
+        };
+        let action = filter.action.as_deref().filter(|v| !v.is_empty());
+        let (admin, search) = (set(&filter.admin), set(&filter.search));
+        let contains = |text: &str, part: &str| text.to_lowercase().contains(part);
+        let state = self.state.lock();
+        Ok(state
+            .audit_log
+            .iter()
+            .rev()
+            .filter(|e| action.is_none_or(|action| e.action == action))
+            .filter(|e| admin.as_ref().is_none_or(|admin| contains(&e.admin, admin)))
+            .filter(|e| {
+                search.as_ref().is_none_or(|search| {
+                    e.target.as_deref().is_some_and(|t| contains(t, search))
+                        || contains(&e.ip_address, search)
+                })
+            })
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+}
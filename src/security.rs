@@ -0,0 +1,169 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest};
+use std::future::{ready, Ready};
+
+use crate::auth::random_bytes;
+use crate::handlers::AppState;
+
+/// Per-request nonce that lets the templates' inline scripts and styles run under
+/// the Content-Security-Policy. Handlers take it as an extractor.
+#[derive(Debug, Clone, Default)]
+pub struct CspNonce(pub String);
+
+impl FromRequest for CspNonce {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<CspNonce>()
+            .cloned()
+            .unwrap_or_default()))
+    }
+}
+
+/// Response header settings, built from the configuration and the saved player images
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SecurityHeaders {
+    /// Extra origins allowed in `img-src`, e.g. an external BANNER_IMAGE host
+    img_sources: Vec<String>,
+    /// HSTS is only sent when this is set, as it must only be used behind HTTPS
+    hsts_max_age: Option<u64>,
+}
+
+/// Whether a value is safe to place in a CSP source list. Anything that could end
+/// the directive or add another one is refused.
+pub fn is_valid_source(source: &str) -> bool {
+    !source.is_empty()
+        && source.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '*' | ':' | '/' | '_' | '~' | '%')
+        })
+}
+
+/// The `scheme://host[:port]` part of an absolute http(s) URL
+pub fn url_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    if !matches!(scheme, "http" | "https") {
+        return None;
+    }
+    let host = rest.split(['/', '?', '#']).next()?;
+    (!host.is_empty()).then(|| format!("{scheme}://{host}"))
+}
+
+impl SecurityHeaders {
+    /// Build the header settings. Returns any image sources that were rejected.
+    pub fn new(
+        img_sources: impl IntoIterator<Item = String>,
+        hsts_max_age: Option<u64>,
+    ) -> (Self, Vec<String>) {
+        let mut valid = Vec::new();
+        let mut invalid = Vec::new();
+        for source in img_sources {
+            if is_valid_source(&source) {
+                if !valid.contains(&source) {
+                    valid.push(source);
+                }
+            } else {
+                invalid.push(source);
+            }
+        }
+
+        (
+            SecurityHeaders {
+                img_sources: valid,
+                hsts_max_age: hsts_max_age.filter(|&age| age > 0),
+            },
+            invalid,
+        )
+    }
+
+    /// The same settings with extra image sources allowed. Invalid sources are skipped.
+    pub fn with_img_sources(&self, sources: impl IntoIterator<Item = String>) -> Self {
+        let mut headers = self.clone();
+        for source in sources {
+            if is_valid_source(&source) && !headers.img_sources.contains(&source) {
+                headers.img_sources.push(source);
+            }
+        }
+        headers
+    }
+
+    pub fn img_sources(&self) -> &[String] {
+        &self.img_sources
+    }
+
+    pub fn hsts_max_age(&self) -> Option<u64> {
+        self.hsts_max_age
+    }
+
+    fn content_security_policy(&self, nonce: &str) -> String {
+        let mut img_src = String::from("'self' data:");
+        for source in &self.img_sources {
+            img_src.push(' ');
+            img_src.push_str(source);
+        }
+
+        format!(
+            "default-src 'self'; \
+             script-src 'self' 'nonce-{nonce}'; \
+             style-src 'self' 'nonce-{nonce}'; \
+             img-src {img_src}; \
+             connect-src 'self'; \
+             object-src 'none'; \
+             base-uri 'none'; \
+             form-action 'self'; \
+             frame-ancestors 'none'"
+        )
+    }
+}
+
+fn generate_nonce() -> String {
+    base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        random_bytes::<16>(),
+    )
+}
+
+/// Middleware adding the security headers to every response
+pub async fn security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let nonce = generate_nonce();
+    req.extensions_mut().insert(CspNonce(nonce.clone()));
+    let settings = req
+        .app_data::<web::Data<AppState>>()
+        .map(|data| data.settings.load().security.clone())
+        .unwrap_or_default();
+
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+
+    if let Ok(value) = HeaderValue::from_str(&settings.content_security_policy(&nonce)) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, value);
+    }
+    headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("same-origin"),
+    );
+    headers.insert(
+        HeaderName::from_static("permissions-policy"),
+        HeaderValue::from_static("camera=(), microphone=(), geolocation=(), payment=(), usb=()"),
+    );
+    if let Some(max_age) = settings.hsts_max_age {
+        if let Ok(value) = HeaderValue::from_str(&format!("max-age={max_age}")) {
+            headers.insert(header::STRICT_TRANSPORT_SECURITY, value);
+        }
+    }
+
+    Ok(res)
+}
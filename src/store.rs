@@ -0,0 +1,122 @@
+use crate::pool::{DbError, DbPool};
+use crate::repository::{FeedbackRepository, RepoResult};
+
+#[cfg(feature = "postgres")]
+use crate::pg::{PgPool, PgRepository};
+
+/// Where feedback, bans, rate-limit counters and the audit log are kept. Admin
+/// accounts, sessions and settings always stay in the local SQLite database.
+///
+/// Closures are given the backend as a `FeedbackRepository`. The `_blocking` methods
+/// must only be called from a blocking thread, async code uses `read` and `write`.
+#[derive(Clone)]
+pub enum FeedbackStore {
+    Sqlite(DbPool),
+    /// Shared by every instance pointed at the same `DATABASE_URL`
+    #[cfg(feature = "postgres")]
+    Postgres(PgPool),
+}
+
+impl FeedbackStore {
+    /// Use PostgreSQL when a database URL is configured, otherwise the SQLite database.
+    /// Connecting blocks, so call this from a blocking thread.
+    pub fn open(database_url: Option<&str>, sqlite: DbPool) -> Result<FeedbackStore, DbError> {
+        match database_url {
+            #[cfg(feature = "postgres")]
+            Some(url) => Ok(FeedbackStore::Postgres(PgPool::connect(url)?)),
+            // Config::load already refuses this, but a Config can be built by hand
+            #[cfg(not(feature = "postgres"))]
+            Some(_) => Err(DbError::Unsupported(
+                "a database URL is set but this build has no PostgreSQL support, rebuild with --features postgres",
+            )),
+            None => Ok(FeedbackStore::Sqlite(sqlite)),
+        }
+    }
+
+    /// Short name for log messages
+    pub fn backend(&self) -> &'static str {
+        match self {
+            FeedbackStore::Sqlite(_) => "SQLite",
+            #[cfg(feature = "postgres")]
+            FeedbackStore::Postgres(_) => "PostgreSQL",
+        }
+    }
+
+    pub fn read_blocking<T>(
+        &self,
+        f: impl FnOnce(&dyn FeedbackRepository) -> RepoResult<T>,
+    ) -> RepoResult<T> {
+        match self {
+            FeedbackStore::Sqlite(pool) => f(&*pool.reader()),
+            #[cfg(feature = "postgres")]
+            FeedbackStore::Postgres(pool) => f(&PgRepository::new(&mut *pool.client()?)),
+        }
+    }
+
+    /// Run `f` in a transaction, committed only if it returns `Ok`
+    pub fn write_blocking<T>(
+        &self,
+        f: impl FnOnce(&dyn FeedbackRepository) -> RepoResult<T>,
+    ) -> RepoResult<T> {
+        match self {
+            FeedbackStore::Sqlite(pool) => {
+                let mut conn = pool.writer();
+                let tx = conn.transaction()?;
+                let result = f(&*tx)?;
+                tx.commit()?;
+                Ok(result)
+            }
+            #[cfg(feature = "postgres")]
+            FeedbackStore::Postgres(pool) => {
+                let mut client = pool.client()?;
+                let mut tx = client.transaction()?;
+                let result = f(&PgRepository::new(&mut tx))?;
+                tx.commit()?;
+                Ok(result)
+            }
+        }
+    }
+
+    /// Run queries that only read, on tokio's blocking thread pool
+    pub async fn read<T, F>(&self, f: F) -> RepoResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn FeedbackRepository) -> RepoResult<T> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || store.read_blocking(f))
+            .await
+            .map_err(|e| DbError::Task(e.to_string()))?
+    }
+
+    /// Run changes in a transaction on tokio's blocking thread pool
+    pub async fn write<T, F>(&self, f: F) -> RepoResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn FeedbackRepository) -> RepoResult<T> + Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || store.write_blocking(f))
+            .await
+            .map_err(|e| DbError::Task(e.to_string()))?
+    }
+}
+
+/// Every hour, permanently delete feedback that has sat in the trash past the
+/// retention window
+pub fn spawn_trash_purge(store: FeedbackStore, retention_days: i64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            let purged = store
+                .write(move |repo| repo.purge_expired(retention_days))
+                .await;
+            match purged {
+                Ok(0) => {}
+                Ok(purged) => log::info!("Purged {purged} expired feedback entries from trash"),
+                Err(e) => log::error!("Failed to purge expired trash: {e}"),
+            }
+        }
+    });
+}
@@ -1,7 +1,14 @@
-use crate::models::Feedback;
+use crate::backup::BackupFile;
+use crate::duties::DutyStats;
+use crate::handlers::AdminIdentity;
+use crate::jobs::RoleStats;
+use crate::models::{AdminAccount, AuditEntry, Feedback, FeedbackSubmission, PlayerSettings, Role};
+use crate::validation::FieldErrors;
+use crate::worlds::{DatacenterCount, WorldRegistry};
 use rinja::Template;
+use std::sync::Arc;
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PlayerConfig {
     pub name: String,
     pub server: String,
@@ -15,6 +22,11 @@ pub struct PlayerConfig {
 #[template(path = "index.html")]
 pub struct IndexTemplate {
     pub player: PlayerConfig,
+    pub nonce: String,
+    pub worlds: Arc<WorldRegistry>,
+    /// Values to fill the form with, empty unless a submission was rejected
+    pub form: FeedbackSubmission,
+    pub errors: FieldErrors,
 }
 
 #[derive(Template)]
@@ -37,17 +49,123 @@ pub struct RateLimitedHardTemplate {
 
 #[derive(Template)]
 #[template(path = "admin_login.html")]
-pub struct AdminLoginTemplate {}
+pub struct AdminLoginTemplate {
+    pub nonce: String,
+    pub error: Option<String>,
+    pub username: String,
+}
 
 #[derive(Template)]
 #[template(path = "default_password_error.html")]
-pub struct DefaultPasswordErrorTemplate {}
+pub struct DefaultPasswordErrorTemplate {
+    pub nonce: String,
+}
+
+/// What the admin navigation bar needs to know about the current admin
+pub struct AdminNav {
+    pub active: &'static str,
+    pub username: String,
+    pub can_moderate: bool,
+    pub is_owner: bool,
+    pub csrf_token: String,
+}
+
+impl AdminNav {
+    pub fn new(active: &'static str, admin: &AdminIdentity) -> Self {
+        AdminNav {
+            active,
+            username: admin.username.clone(),
+            can_moderate: admin.role.allows(Role::Moderator),
+            is_owner: admin.role.allows(Role::Owner),
+            csrf_token: admin.csrf_token.clone(),
+        }
+    }
+}
 
 #[derive(Template)]
 #[template(path = "admin.html")]
 pub struct AdminTemplate {
+    pub nav: AdminNav,
+    pub nonce: String,
     pub player: PlayerConfig,
     pub feedbacks: Vec<Feedback>,
     pub total_count: usize,
     pub avg_overall: f32,
+    pub trash_count: i64,
+    pub worlds: Arc<WorldRegistry>,
+    /// Live feedback per data center of the stated world
+    pub by_datacenter: Vec<DatacenterCount>,
+    pub by_role: Vec<RoleStats>,
+    /// Live feedback per duty, worst rated first
+    pub by_duty: Vec<DutyStats>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_trash.html")]
+pub struct AdminTrashTemplate {
+    pub nav: AdminNav,
+    pub nonce: String,
+    pub player: PlayerConfig,
+    pub feedbacks: Vec<Feedback>,
+    pub retention_days: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin_audit.html")]
+pub struct AdminAuditTemplate {
+    pub nav: AdminNav,
+    pub player: PlayerConfig,
+    pub entries: Vec<AuditEntry>,
+    pub actions: Vec<&'static str>,
+    pub filter_action: String,
+    pub filter_admin: String,
+    pub filter_search: String,
+}
+
+#[derive(Template)]
+#[template(path = "admin_users.html")]
+pub struct AdminUsersTemplate {
+    pub nav: AdminNav,
+    pub nonce: String,
+    pub player: PlayerConfig,
+    pub admins: Vec<AdminAccount>,
+    pub roles: Vec<&'static str>,
+    pub legacy_login: bool,
+    pub min_password_length: usize,
+}
+
+#[derive(Template)]
+#[template(path = "admin_settings.html")]
+pub struct AdminSettingsTemplate {
+    pub nav: AdminNav,
+    pub nonce: String,
+    /// The profile as currently shown, used for the preview
+    pub player: PlayerConfig,
+    /// Values from the config file and environment, shown as placeholders
+    pub defaults: PlayerConfig,
+    pub overrides: PlayerSettings,
+    pub worlds: Arc<WorldRegistry>,
+    pub backups: Vec<BackupFile>,
+    pub backup_keep: usize,
+}
+
+#[derive(Template)]
+#[template(path = "admin_account.html")]
+pub struct AdminAccountTemplate {
+    pub nav: AdminNav,
+    pub nonce: String,
+    pub player: PlayerConfig,
+    /// None when signed in with the legacy shared password
+    pub account: Option<AdminAccount>,
+    pub recovery_codes_left: i64,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorTemplate {
+    pub status: u16,
+    pub title: &'static str,
+    pub message: String,
+    pub correlation_id: String,
+    pub back_url: &'static str,
 }
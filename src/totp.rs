@@ -0,0 +1,216 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::auth::{constant_time_eq, random_bytes};
+
+// RFC 6238 defaults, which is what authenticator apps assume from an otpauth URI
+pub const STEP_SECONDS: u64 = 30;
+pub const DIGITS: u32 = 6;
+// Accept codes one step either side of now to allow for clock drift
+const ALLOWED_SKEW_STEPS: u64 = 1;
+const SECRET_BYTES: usize = 20;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, as used in otpauth URIs
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(5) * 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+pub fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for c in input.chars().filter(|c| !matches!(c, '=' | ' ' | '-')) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// A fresh random secret, base32 encoded for storage and display
+pub fn generate_secret() -> String {
+    base32_encode(&random_bytes::<SECRET_BYTES>())
+}
+
+/// RFC 4226 HOTP value for one counter
+pub fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(digits)
+}
+
+/// Check a user-entered code against the secret at `unix_time`.
+/// Returns the time step that matched, so callers can refuse to accept it twice.
+pub fn verify(secret_base32: &str, code: &str, unix_time: u64) -> Option<u64> {
+    let secret = base32_decode(secret_base32)?;
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let current = unix_time / STEP_SECONDS;
+    (current.saturating_sub(ALLOWED_SKEW_STEPS)..=current + ALLOWED_SKEW_STEPS).find(|&step| {
+        let expected = format!(
+            "{:0width$}",
+            hotp(&secret, step, DIGITS),
+            width = DIGITS as usize
+        );
+        constant_time_eq(expected.as_bytes(), code.as_bytes())
+    })
+}
+
+fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The otpauth:// URI authenticator apps import, usually via QR code
+pub fn provisioning_uri(secret_base32: &str, issuer: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        uri_encode(issuer),
+        uri_encode(account),
+        secret_base32,
+        uri_encode(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+/// Render the provisioning URI as an inline SVG QR code
+pub fn qr_code_svg(uri: &str) -> Option<String> {
+    let code = qrcode::QrCode::new(uri.as_bytes()).ok()?;
+    Some(
+        code.render::<qrcode::render::svg::Color>()
+            .min_dimensions(200, 200)
+            .quiet_zone(true)
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The shared secret from RFC 4226 Appendix D and RFC 6238 Appendix B
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn rfc_secret_base32() -> String {
+        base32_encode(RFC_SECRET)
+    }
+
+    #[test]
+    fn hotp_matches_rfc4226_vectors() {
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (counter, code) in expected.into_iter().enumerate() {
+            assert_eq!(
+                hotp(RFC_SECRET, counter as u64, 6),
+                code,
+                "counter {counter}"
+            );
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc6238_sha1_vectors() {
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+        for (time, code) in vectors {
+            let step = time / STEP_SECONDS;
+            assert_eq!(hotp(RFC_SECRET, step, 8), code, "T={time}");
+            assert_eq!(hotp(RFC_SECRET, step, 6), code % 1_000_000, "T={time}");
+        }
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        assert_eq!(rfc_secret_base32(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        for len in 0..=SECRET_BYTES {
+            let data: Vec<u8> = (0..len as u8).map(|b| b.wrapping_mul(37)).collect();
+            assert_eq!(
+                base32_decode(&base32_encode(&data)).as_deref(),
+                Some(&data[..])
+            );
+        }
+        // Authenticator apps show secrets lowercased and grouped
+        assert_eq!(
+            base32_decode("gezd gnbv-gy3t qojq gezd gnbv gy3t qojq").as_deref(),
+            Some(RFC_SECRET)
+        );
+        assert_eq!(base32_decode("GEZ1"), None);
+    }
+
+    #[test]
+    fn verify_accepts_one_step_of_skew() {
+        let secret = rfc_secret_base32();
+        // 081804 is the code for the step containing T=1111111109
+        let time = 1111111109;
+        let step = time / STEP_SECONDS;
+        assert_eq!(verify(&secret, "081804", time), Some(step));
+        assert_eq!(verify(&secret, " 081 804 ", time), Some(step));
+        assert_eq!(verify(&secret, "081804", time - STEP_SECONDS), Some(step));
+        assert_eq!(verify(&secret, "081804", time + STEP_SECONDS), Some(step));
+        assert_eq!(verify(&secret, "081804", time - 2 * STEP_SECONDS), None);
+        assert_eq!(verify(&secret, "081804", time + 2 * STEP_SECONDS), None);
+    }
+
+    #[test]
+    fn verify_rejects_malformed_codes() {
+        let secret = rfc_secret_base32();
+        let time = 1111111109;
+        assert_eq!(verify(&secret, "81804", time), None);
+        assert_eq!(verify(&secret, "07081804", time), None);
+        assert_eq!(verify(&secret, "08180a", time), None);
+        assert_eq!(verify(&secret, "", time), None);
+        assert_eq!(verify("not base32!", "081804", time), None);
+    }
+}
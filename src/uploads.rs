@@ -0,0 +1,191 @@
+use actix_files::NamedFile;
+use actix_web::{web, HttpRequest, HttpResponse};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::handlers::AppState;
+
+/// Largest upload accepted, before decoding
+pub const MAX_UPLOAD_BYTES: usize = 5 * 1024 * 1024;
+
+// Decoding is refused beyond these, so a small file can't expand into a huge image
+const MAX_SOURCE_DIMENSION: u32 = 8000;
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The images that can be uploaded from the settings page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    Banner,
+    Profile,
+}
+
+impl ImageKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "banner" => Some(ImageKind::Banner),
+            "profile" => Some(ImageKind::Profile),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageKind::Banner => "banner",
+            ImageKind::Profile => "profile",
+        }
+    }
+
+    /// Output size. The banner matches the template's 1920x220, the profile picture is
+    /// twice its 110px display size so it stays sharp on high-DPI screens.
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            ImageKind::Banner => (1920, 220),
+            ImageKind::Profile => (220, 220),
+        }
+    }
+}
+
+/// Identify an upload by its magic bytes rather than trusting the client's content type
+fn detect_format(bytes: &[u8]) -> Option<ImageFormat> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageFormat::Png)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(ImageFormat::Jpeg)
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some(ImageFormat::WebP)
+    } else {
+        None
+    }
+}
+
+fn decode(bytes: &[u8], format: ImageFormat) -> image::ImageResult<DynamicImage> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+    // Rotate as the camera intended, since the EXIF data saying so is dropped
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Validate an uploaded PNG, JPEG or WebP and re-encode it as a WebP cropped to the
+/// size the templates expect. Only the pixels are kept, so EXIF and other metadata
+/// never reach the output.
+pub fn process_image(bytes: &[u8], kind: ImageKind) -> Result<Vec<u8>, String> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(format!(
+            "Images must be at most {} MB",
+            MAX_UPLOAD_BYTES / 1024 / 1024
+        ));
+    }
+    let format = detect_format(bytes).ok_or("Only PNG, JPEG and WebP images are supported")?;
+    let image = decode(bytes, format).map_err(|e| format!("Could not read image: {e}"))?;
+
+    let (width, height) = kind.dimensions();
+    let resized = image.resize_to_fill(width, height, FilterType::Lanczos3);
+
+    let mut output = Vec::new();
+    resized
+        .to_rgba8()
+        .write_with_encoder(WebPEncoder::new_lossless(&mut output))
+        .map_err(|e| format!("Could not encode image: {e}"))?;
+    Ok(output)
+}
+
+/// Write a processed image to the uploads directory, returning its file name
+pub fn store_image(dir: &Path, kind: ImageKind, webp: &[u8]) -> std::io::Result<String> {
+    std::fs::create_dir_all(dir)?;
+    let file_name = format!("{}-{}.webp", kind.as_str(), uuid::Uuid::new_v4());
+    std::fs::write(dir.join(&file_name), webp)?;
+    Ok(file_name)
+}
+
+/// Whether `name` is one `store_image` could have written. The route's path segment
+/// is percent-decoded, so anything looser could name a file outside the directory.
+fn is_upload_name(name: &str) -> bool {
+    let Some((kind, id)) = name
+        .strip_suffix(".webp")
+        .and_then(|stem| stem.split_once('-'))
+    else {
+        return false;
+    };
+    ImageKind::parse(kind).is_some()
+        && uuid::Uuid::try_parse(id).is_ok_and(|uuid| uuid.hyphenated().to_string() == id)
+}
+
+/// Serve an image from the uploads directory, at /uploads
+pub async fn serve(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    name: web::Path<String>,
+) -> HttpResponse {
+    if !is_upload_name(&name) {
+        return HttpResponse::NotFound().finish();
+    }
+    match NamedFile::open(data.uploads_dir.join(name.as_str())) {
+        Ok(file) => file.use_last_modified(true).into_response(&req),
+        Err(_) => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_stored_image_names() {
+        let dir = std::env::temp_dir().join(format!("uploads-test-{}", uuid::Uuid::new_v4()));
+        let name = store_image(&dir, ImageKind::Banner, b"webp").unwrap();
+        assert!(is_upload_name(&name));
+        assert!(is_upload_name(
+            "profile-67e55044-10b1-426f-9247-bb680e5fe0c8.webp"
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        assert!(!is_upload_name("/etc/passwd"));
+        assert!(!is_upload_name(
+            "/banner-67e55044-10b1-426f-9247-bb680e5fe0c8.webp"
+        ));
+    }
+
+    #[test]
+    fn rejects_parent_directories() {
+        assert!(!is_upload_name("sub/../../x"));
+        assert!(!is_upload_name(
+            "banner-../../67e55044-10b1-426f-9247-bb680e5fe0c8.webp"
+        ));
+        assert!(!is_upload_name(
+            "banner-67e55044-10b1-426f-9247-bb680e5fe0c8.webp/../../x.webp"
+        ));
+    }
+
+    #[test]
+    fn rejects_other_names() {
+        assert!(!is_upload_name(""));
+        assert!(!is_upload_name(".env"));
+        assert!(!is_upload_name(
+            "avatar-67e55044-10b1-426f-9247-bb680e5fe0c8.webp"
+        ));
+        assert!(!is_upload_name(
+            "banner-67e55044-10b1-426f-9247-bb680e5fe0c8.png"
+        ));
+        assert!(!is_upload_name(
+            "banner-67e5504410b1426f9247bb680e5fe0c8.webp"
+        ));
+        assert!(!is_upload_name(
+            "banner-{67e55044-10b1-426f-9247-bb680e5fe0c8}.webp"
+        ));
+    }
+}
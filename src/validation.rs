@@ -0,0 +1,289 @@
+use std::fmt;
+
+use crate::duties::{self, Duty};
+use crate::jobs::Job;
+use crate::models::{ContentType, Feedback, FeedbackSubmission};
+use crate::worlds::WorldRegistry;
+
+// Maximum allowed lengths for text fields to avoid unbounded DB growth
+pub const MAX_CHAR_NAME: usize = 100;
+pub const MAX_SERVER: usize = 50;
+pub const MAX_COMMENTS: usize = 200;
+
+/// Problems found in a submission, keyed by form field name so the form can be shown
+/// again with each message beside its field. Problems with the submission as a whole
+/// are filed under `"form"`.
+#[derive(Debug, Default)]
+pub struct FieldErrors(Vec<(&'static str, String)>);
+
+impl FieldErrors {
+    fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.push((field, message.into()));
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, message)| message.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for FieldErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (field, message)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{field}: {message}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Feedback that passed `validate_feedback`, which is the only way to get one. Text is
+/// trimmed, blank fields are `None`, and the identity is dropped when anonymous.
+#[derive(Debug, Clone)]
+pub struct ValidatedFeedback {
+    character_name: Option<String>,
+    server: Option<String>,
+    is_anonymous: bool,
+    rating_mechanics: i32,
+    rating_damage: i32,
+    rating_teamwork: i32,
+    rating_communication: i32,
+    rating_overall: i32,
+    comments: Option<String>,
+    content_type: Option<ContentType>,
+    player_job: Option<Job>,
+    duty: Option<&'static Duty>,
+}
+
+impl ValidatedFeedback {
+    /// A new, unpublished feedback entry
+    pub fn into_feedback(self, id: String, ip_address: String, created_at: String) -> Feedback {
+        Feedback {
+            id,
+            character_name: self.character_name,
+            server: self.server,
+            is_anonymous: self.is_anonymous,
+            rating_mechanics: self.rating_mechanics,
+            rating_damage: self.rating_damage,
+            rating_teamwork: self.rating_teamwork,
+            rating_communication: self.rating_communication,
+            rating_overall: self.rating_overall,
+            comments: self.comments,
+            content_type: self
+                .content_type
+                .map(|content| content.as_str().to_string()),
+            player_job: self.player_job.map(|job| job.as_str().to_string()),
+            duty: self.duty.map(|duty| duty.name.clone()),
+            ip_address,
+            created_at,
+            deleted_at: None,
+            is_published: false,
+            tags: Vec::new(),
+        }
+    }
+}
+
+impl From<&Feedback> for FeedbackSubmission {
+    /// Stored feedback in the shape of a submission, so imports are checked like the form
+    fn from(feedback: &Feedback) -> Self {
+        FeedbackSubmission {
+            character_name: feedback.character_name.clone(),
+            server: feedback.server.clone(),
+            is_anonymous: feedback.is_anonymous,
+            rating_mechanics: Some(feedback.rating_mechanics),
+            rating_damage: Some(feedback.rating_damage),
+            rating_teamwork: Some(feedback.rating_teamwork),
+            rating_communication: Some(feedback.rating_communication),
+            rating_overall: Some(feedback.rating_overall),
+            comments: feedback.comments.clone(),
+            content_type: feedback.content_type.clone(),
+            player_job: feedback.player_job.clone(),
+            duty: feedback.duty.clone(),
+        }
+    }
+}
+
+/// A trimmed text field, `None` when left blank
+fn text(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// Check a text field against its length limit
+fn limited(
+    errors: &mut FieldErrors,
+    field: &'static str,
+    value: Option<&str>,
+    max_chars: usize,
+) -> Option<String> {
+    let value = text(value)?;
+    if value.chars().count() > max_chars {
+        errors.add(field, format!("Keep this under {max_chars} characters"));
+    }
+    Some(value.to_string())
+}
+
+/// Check a choice from one of the form's lists
+fn choice<T>(
+    errors: &mut FieldErrors,
+    field: &'static str,
+    value: Option<&str>,
+    parse: fn(&str) -> Option<T>,
+    message: &str,
+) -> Option<T> {
+    let value = text(value)?;
+    let parsed = parse(value);
+    if parsed.is_none() {
+        errors.add(field, message);
+    }
+    parsed
+}
+
+/// Check if any field contains filter words (case-insensitive)
+fn contains_filter_words(text: Option<&str>, filter_words: &[String]) -> bool {
+    if let Some(text) = text {
+        let text_lower = text.to_lowercase();
+        filter_words.iter().any(|word| text_lower.contains(word))
+    } else {
+        false
+    }
+}
+
+/// Check if feedback contains any filter words
+fn feedback_contains_filtered_words(form: &FeedbackSubmission, filter_words: &[String]) -> bool {
+    contains_filter_words(form.character_name.as_deref(), filter_words)
+        || contains_filter_words(form.server.as_deref(), filter_words)
+        || contains_filter_words(form.comments.as_deref(), filter_words)
+        || contains_filter_words(form.content_type.as_deref(), filter_words)
+        || contains_filter_words(form.player_job.as_deref(), filter_words)
+        || contains_filter_words(form.duty.as_deref(), filter_words)
+}
+
+/// Check feedback from the form, an import or any other source, collecting every
+/// problem rather than stopping at the first
+pub fn validate_feedback(
+    form: &FeedbackSubmission,
+    filter_words: &[String],
+    worlds: &WorldRegistry,
+) -> Result<ValidatedFeedback, FieldErrors> {
+    let mut errors = FieldErrors::default();
+
+    if feedback_contains_filtered_words(form, filter_words) {
+        errors.add(
+            "form",
+            "Your feedback contains words that aren't allowed, please reword it",
+        );
+    }
+
+    let [rating_mechanics, rating_damage, rating_teamwork, rating_communication, rating_overall] =
+        [
+            (
+                form.rating_mechanics,
+                "rating_mechanics",
+                "Mechanics Execution",
+            ),
+            (form.rating_damage, "rating_damage", "Damage/Healing Output"),
+            (form.rating_teamwork, "rating_teamwork", "Teamwork"),
+            (
+                form.rating_communication,
+                "rating_communication",
+                "Communication",
+            ),
+            (form.rating_overall, "rating_overall", "Overall Experience"),
+        ]
+        .map(|(rating, field, label)| {
+            rating
+                .filter(|rating| (1..=5).contains(rating))
+                .unwrap_or_else(|| {
+                    errors.add(field, format!("Please rate {label}"));
+                    0
+                })
+        });
+
+    // The identity is ignored for anonymous feedback
+    let (character_name, server) = if form.is_anonymous {
+        (None, None)
+    } else {
+        let character_name = limited(
+            &mut errors,
+            "character_name",
+            form.character_name.as_deref(),
+            MAX_CHAR_NAME,
+        );
+        // Worlds are stored with the spelling from the world list
+        let server = text(form.server.as_deref()).and_then(|server| {
+            let world = worlds
+                .find_world(server)
+                .filter(|_| server.chars().count() <= MAX_SERVER);
+            if world.is_none() {
+                errors.add("server", "Choose a server from the list");
+            }
+            world.map(|(_, world)| world.name.clone())
+        });
+        (character_name, server)
+    };
+
+    let comments = limited(
+        &mut errors,
+        "comments",
+        form.comments.as_deref(),
+        MAX_COMMENTS,
+    );
+    let content_type = choice(
+        &mut errors,
+        "content_type",
+        form.content_type.as_deref(),
+        ContentType::parse,
+        "Choose a content type from the list",
+    );
+    let duty = choice(
+        &mut errors,
+        "duty",
+        form.duty.as_deref(),
+        |name| duties::catalogue().find(name),
+        "Choose a duty from the list",
+    );
+    // A duty implies its content type, so picking only the duty is enough
+    let content_type = match (content_type, duty) {
+        (Some(content), Some(duty)) if content != duty.category => {
+            errors.add(
+                "duty",
+                format!("{} is not a {} duty", duty.name, content.as_str()),
+            );
+            Some(content)
+        }
+        (content, duty) => content.or(duty.map(|duty| duty.category)),
+    };
+    let player_job = choice(
+        &mut errors,
+        "player_job",
+        form.player_job.as_deref(),
+        Job::parse,
+        "Choose a job from the list",
+    );
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(ValidatedFeedback {
+        character_name,
+        server,
+        is_anonymous: form.is_anonymous,
+        rating_mechanics,
+        rating_damage,
+        rating_teamwork,
+        rating_communication,
+        rating_overall,
+        comments,
+        content_type,
+        player_job,
+        duty,
+    })
+}
@@ -0,0 +1,170 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The world list built into the binary, used unless `data.worlds_file` points elsewhere
+const BUILTIN_WORLDS: &str = include_str!("../data/worlds.toml");
+
+/// A world players can have as their home server
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct World {
+    pub name: String,
+    /// Characters can visit but not be created or transferred here
+    #[serde(default)]
+    pub travel_only: bool,
+    /// Character creation is restricted
+    #[serde(default)]
+    pub congested: bool,
+}
+
+/// An FFXIV data center and the worlds that belong to it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Datacenter {
+    pub name: String,
+    pub region: String,
+    pub worlds: Vec<World>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorldsFile {
+    datacenters: Vec<Datacenter>,
+}
+
+/// Live feedback from the worlds of one data center, see `WorldRegistry::count_by_datacenter`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatacenterCount {
+    pub region: String,
+    pub datacenter: String,
+    pub count: i64,
+}
+
+/// Every known world with its data center and region, in display order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldRegistry {
+    datacenters: Vec<Datacenter>,
+}
+
+impl Default for WorldRegistry {
+    fn default() -> Self {
+        WorldRegistry::parse(BUILTIN_WORLDS).expect("the built-in world list is valid")
+    }
+}
+
+impl WorldRegistry {
+    /// Read the world list from a file, or use the built-in one
+    pub fn load(path: Option<&Path>) -> Result<WorldRegistry, String> {
+        let Some(path) = path else {
+            return Ok(WorldRegistry::default());
+        };
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        WorldRegistry::parse(&contents).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Parse a world list, rejecting blank names and worlds or data centers listed twice
+    pub fn parse(contents: &str) -> Result<WorldRegistry, String> {
+        let file: WorldsFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+        if file.datacenters.is_empty() {
+            return Err("no data centers are listed".to_string());
+        }
+
+        let mut datacenter_names = HashSet::new();
+        let mut world_names = HashSet::new();
+        for dc in &file.datacenters {
+            if dc.name.trim().is_empty() || dc.region.trim().is_empty() {
+                return Err("every data center needs a name and a region".to_string());
+            }
+            if !datacenter_names.insert(dc.name.to_lowercase()) {
+                return Err(format!("data center {} is listed twice", dc.name));
+            }
+            if dc.worlds.is_empty() {
+                return Err(format!("data center {} has no worlds", dc.name));
+            }
+            for world in &dc.worlds {
+                if world.name.trim().is_empty() {
+                    return Err(format!(
+                        "data center {} has a world without a name",
+                        dc.name
+                    ));
+                }
+                if !world_names.insert(world.name.to_lowercase()) {
+                    return Err(format!("world {} is listed twice", world.name));
+                }
+            }
+        }
+
+        Ok(WorldRegistry {
+            datacenters: file.datacenters,
+        })
+    }
+
+    pub fn datacenters(&self) -> &[Datacenter] {
+        &self.datacenters
+    }
+
+    /// A world and the data center it belongs to, matched case-insensitively
+    pub fn find_world(&self, server: &str) -> Option<(&Datacenter, &World)> {
+        let server = server.trim();
+        self.datacenters.iter().find_map(|dc| {
+            dc.worlds
+                .iter()
+                .find(|world| world.name.eq_ignore_ascii_case(server))
+                .map(|world| (dc, world))
+        })
+    }
+
+    /// The data center a world belongs to
+    pub fn find_datacenter(&self, server: &str) -> Option<&Datacenter> {
+        self.find_world(server).map(|(dc, _)| dc)
+    }
+
+    /// A data center by name, matched case-insensitively
+    pub fn datacenter_named(&self, name: &str) -> Option<&Datacenter> {
+        let name = name.trim();
+        self.datacenters
+            .iter()
+            .find(|dc| dc.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_valid_server(&self, server: &str) -> bool {
+        self.find_world(server).is_some()
+    }
+
+    /// The name of a world's data center, empty when the world is unknown or not given
+    pub fn datacenter_of(&self, server: Option<&str>) -> &str {
+        server
+            .and_then(|server| self.find_datacenter(server))
+            .map_or("", |dc| dc.name.as_str())
+    }
+
+    /// Add up per-world feedback counts by data center, in display order. Data centers
+    /// without feedback are left out, as are worlds no longer in the list.
+    pub fn count_by_datacenter<'a>(
+        &self,
+        per_world: impl IntoIterator<Item = (&'a str, i64)>,
+    ) -> Vec<DatacenterCount> {
+        let mut counts = vec![0; self.datacenters.len()];
+        for (server, count) in per_world {
+            if let Some(i) = self.datacenters.iter().position(|dc| {
+                dc.worlds
+                    .iter()
+                    .any(|w| w.name.eq_ignore_ascii_case(server))
+            }) {
+                counts[i] += count;
+            }
+        }
+        self.datacenters
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| *count > 0)
+            .map(|(dc, count)| DatacenterCount {
+                region: dc.region.clone(),
+                datacenter: dc.name.clone(),
+                count,
+            })
+            .collect()
+    }
+}